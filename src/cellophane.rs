@@ -0,0 +1,113 @@
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+use crate::theme::{Color, Theme};
+
+// localStorageに保存するときのキー
+const LOCAL_STORAGE_KEY: &str = "invader-yew-cellophane";
+// 設定画面に表示する各範囲の名前
+pub const BAND_LABELS: [&str; 2] = ["UFO Band", "Player Band"];
+
+// 画面の上端からtop以上bottom未満の範囲に貼る色付きのセロハン [pixel]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub top: f64,
+    pub bottom: f64,
+    pub color: Color,
+}
+
+// 白黒の画面にセロハンを貼って色を付けていた、元のアーケード版の見た目にする設定
+// 有効な場合は画像をすべて白で描き、描画後に縦方向の範囲ごとに色を付ける
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cellophane {
+    pub enabled: bool,
+    pub bands: [Band; 2], // UFOの高さの赤と、トーチカからプレイヤーまでの緑
+}
+
+impl Default for Cellophane {
+    fn default() -> Self {
+        Cellophane {
+            enabled: false,
+            bands: [
+                Band {
+                    top: 60.,
+                    bottom: 100.,
+                    color: Color::Red,
+                },
+                Band {
+                    top: 400.,
+                    bottom: 600.,
+                    color: Color::Green,
+                },
+            ],
+        }
+    }
+}
+
+impl Cellophane {
+    // 保存された設定を読み込む(無ければ初期設定)
+    pub fn load() -> Self {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        storage
+            .and_then(|storage| storage.get_item(LOCAL_STORAGE_KEY).ok().flatten())
+            .and_then(|text| Cellophane::from_text(&text))
+            .unwrap_or_default()
+    }
+    pub fn save(&self) {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        if let Some(storage) = storage {
+            if storage
+                .set_item(LOCAL_STORAGE_KEY, &self.to_text())
+                .is_err()
+            {
+                log::warn!("セロハンの設定を保存できませんでした。");
+            }
+        }
+    }
+    // "有効なら1 各範囲の上端 下端 ..."の形式
+    fn to_text(&self) -> String {
+        let mut values = vec![(self.enabled as u8).to_string()];
+        for band in &self.bands {
+            values.push(band.top.to_string());
+            values.push(band.bottom.to_string());
+        }
+        values.join(" ")
+    }
+    fn from_text(text: &str) -> Option<Self> {
+        let mut values = text.split_whitespace();
+        let mut cellophane = Cellophane {
+            enabled: values.next()? == "1",
+            ..Cellophane::default()
+        };
+        for i in 0..cellophane.bands.len() {
+            let top = values.next()?.parse().ok()?;
+            let bottom = values.next()?.parse().ok()?;
+            cellophane.set_band(i, top, bottom);
+        }
+        Some(cellophane)
+    }
+    // index番目の範囲を変える(上端が下端より下にならないように揃える)
+    pub fn set_band(&mut self, index: usize, top: f64, bottom: f64) {
+        if let Some(band) = self.bands.get_mut(index) {
+            if top.is_finite() && bottom.is_finite() {
+                band.top = top.max(0.);
+                band.bottom = bottom.max(band.top);
+            }
+        }
+    }
+    // 白で描いた画面screenをctxに写し、範囲ごとにthemeの色を掛け合わせる
+    pub fn composite(
+        &self,
+        ctx: &CanvasRenderingContext2d,
+        screen: &HtmlCanvasElement,
+        theme: &Theme,
+    ) {
+        ctx.draw_image_with_html_canvas_element(screen, 0., 0.)
+            .unwrap();
+        ctx.set_global_composite_operation("multiply").unwrap();
+        for band in &self.bands {
+            ctx.set_fill_style(&theme.style(band.color));
+            ctx.fill_rect(0., band.top, screen.width() as f64, band.bottom - band.top);
+        }
+        ctx.set_global_composite_operation("source-over").unwrap();
+    }
+}
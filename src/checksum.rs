@@ -0,0 +1,44 @@
+use crate::math::Vec2;
+
+// ゲームの状態が一致しているか比べるための64bitハッシュ(FNV-1a)
+pub struct Checksum {
+    hash: u64,
+}
+
+impl Checksum {
+    pub fn new() -> Self {
+        Checksum {
+            hash: 0xCBF2_9CE4_8422_2325,
+        }
+    }
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+    pub fn write_i32(&mut self, value: i32) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bytes(&[value as u8]);
+    }
+    // 浮動小数点数はビット表現をそのまま使う
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+    pub fn write_vec2(&mut self, value: &Vec2) {
+        self.write_f64(value.x);
+        self.write_f64(value.y);
+    }
+    pub fn write_option_i32(&mut self, value: Option<i32>) {
+        self.write_bool(value.is_some());
+        self.write_i32(value.unwrap_or(0));
+    }
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
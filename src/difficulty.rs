@@ -0,0 +1,101 @@
+// タイトル画面で選ぶ難易度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    Arcade, // 元のアーケード版に近い設定
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::Arcade,
+    ];
+    // リプレイファイルに保存する番号
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Difficulty::Normal => 0,
+            Difficulty::Easy => 1,
+            Difficulty::Hard => 2,
+            Difficulty::Arcade => 3,
+        }
+    }
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.to_u8() == value)
+    }
+    // 画面に表示する名前
+    pub fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+            Difficulty::Arcade => "ARCADE",
+        }
+    }
+    // 次の難易度(最後の次は最初に戻る)
+    pub fn next(self) -> Self {
+        let index = Difficulty::ALL
+            .iter()
+            .position(|difficulty| *difficulty == self)
+            .unwrap_or(0);
+        Difficulty::ALL[(index + 1) % Difficulty::ALL.len()]
+    }
+    pub fn config(self) -> DifficultyConfig {
+        match self {
+            Difficulty::Easy => DifficultyConfig {
+                enemy_bullet_speed: 2.5,
+                enemy_shot_interval: 100,
+                invader_step: 6.,
+                invader_drop: 6. * 2.3,
+                ufo_interval: 20 * 60,
+                player_speed: 4.,
+                player_lives: 5,
+            },
+            Difficulty::Normal => DifficultyConfig {
+                enemy_bullet_speed: 3.,
+                enemy_shot_interval: 70,
+                invader_step: 7.,
+                invader_drop: 8. * 2.3,
+                ufo_interval: 25 * 60,
+                player_speed: 3.5,
+                player_lives: 3,
+            },
+            Difficulty::Hard => DifficultyConfig {
+                enemy_bullet_speed: 4.5,
+                enemy_shot_interval: 40,
+                invader_step: 9.,
+                invader_drop: 10. * 2.3,
+                // 高得点のUFOを狙える機会も減らすため、UFOは標準より出にくくする
+                ufo_interval: 30 * 60,
+                player_speed: 3.5,
+                player_lives: 3,
+            },
+            Difficulty::Arcade => DifficultyConfig {
+                enemy_bullet_speed: 4.,
+                enemy_shot_interval: 48,
+                invader_step: 2. * 2.3,
+                invader_drop: 8. * 2.3,
+                ufo_interval: 1536, // 25.6秒
+                player_speed: 2.5,
+                player_lives: 3,
+            },
+        }
+    }
+}
+
+// 難易度によって変わる値
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DifficultyConfig {
+    pub enemy_bullet_speed: f64,    // 敵弾の1フレームの移動距離 [pixel]
+    pub enemy_shot_interval: usize, // 敵が前回の射撃から次の弾を撃つまでの最短フレーム数
+    pub invader_step: f64,          // インベーダーの1回の横移動距離 [pixel]
+    pub invader_drop: f64,          // 移動方向が反転する時のインベーダーの下への移動距離 [pixel]
+    pub ufo_interval: i32,          // UFOの前回の出現から次の出現までのフレーム数
+    pub player_speed: f64,          // プレイヤーの1フレームの移動距離 [pixel]
+    pub player_lives: i32,          // ゲーム開始時の自機含む残機
+}
@@ -1,14 +1,30 @@
+use anyhow::{bail, ensure};
 use std::io::Write;
 
-pub enum Color {
-    Turquoise,    // 青緑色
-    PlayerBullet, // プレイヤーの弾専用色
-    Purple,       // 紫色
-    Green,        // 緑色
-    Red,          // 赤色
-    Yellow,       //黄色
-    Background,   // 背景色
-}
+// 画像として描くドットマップの名前(画像パックやドット絵エディタで差し替えられる)
+// トーチカの形は当たり判定に使うため、ret_dot_dataで取得できても含めない
+pub const DOT_NAMES: [&str; 18] = [
+    "player",
+    "player_bullet",
+    "crab_down",
+    "crab_banzai",
+    "octopus_open",
+    "octopus_close",
+    "squid_open",
+    "squid_close",
+    "explosion",
+    "land_player_bullet",
+    "ufo",
+    "ufo_explosion",
+    "enemy_bullet_squiggly",
+    "enemy_bullet_plunger",
+    "enemy_bullet_rolling",
+    "enemy_bullet_explosion",
+    "player_explosion_1",
+    "player_explosion_2",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DotShape {
     pub width: u32,              // 幅[ドット]
     pub height: u32,             // 高さ[ドット]
@@ -17,23 +33,25 @@ pub struct DotShape {
 
 impl DotShape {
     // 真偽値で表されたドットマップを、1pixelをrgbaの4バイトで表すVec<u8>に変換
-    pub fn create_color_dot_map(&self, color: Color) -> Vec<u8> {
+    pub fn create_color_dot_map(&self, color: [u8; 4]) -> anyhow::Result<Vec<u8>> {
         // 指定されたサイズと実際のドットマップのサイズが一致しているか確認
-        if self.height as usize != self.dot_map.len() {
-            panic!("指定されたドットマップの高さが実際のデータと異なります。");
-        }
-        if self.width as usize != self.dot_map[0].len() {
-            panic!("指定されたドットマップの幅が実際のデータと異なります。");
-        }
+        ensure!(
+            self.height as usize == self.dot_map.len(),
+            "指定されたドットマップの高さが実際のデータと異なります"
+        );
+        ensure!(
+            self.dot_map
+                .first()
+                .is_some_and(|line| self.width as usize == line.len()),
+            "指定されたドットマップの幅が実際のデータと異なります"
+        );
         // ドットマップの幅が異なる行が無いか確認
-        let map_width = self.dot_map[0].len();
-        for l in &self.dot_map {
-            if l.len() != map_width {
-                panic!("ドットマップの形が不正です。");
-            }
-        }
-        // ドット絵を描画する部分
-        let color = set_color(color);
+        ensure!(
+            self.dot_map
+                .iter()
+                .all(|line| line.len() == self.width as usize),
+            "ドットマップの形が不正です"
+        );
         // 背景を透過する部分
         let transparent = vec![0, 0, 0, 0];
 
@@ -41,18 +59,91 @@ impl DotShape {
         for line in &self.dot_map {
             for c in line {
                 if *c {
-                    bytes.write(&color).unwrap();
+                    bytes.write_all(&color).unwrap();
                 } else {
-                    bytes.write(&transparent).unwrap();
+                    bytes.write_all(&transparent).unwrap();
                 }
             }
         }
-        bytes
+        Ok(bytes)
+    }
+    // ret_dot_dataの中身にそのまま貼り付けられる"# _"形式の文字列に変換
+    pub fn to_source(&self) -> String {
+        self.dot_map
+            .iter()
+            .map(|line| {
+                let dots: Vec<&str> = line.iter().map(|c| if *c { "#" } else { "_" }).collect();
+                format!("\"{}\",\n", dots.join(" "))
+            })
+            .collect()
+    }
+    // "# _"形式の文字列から作る
+    // ソースコードからコピーした引用符やカンマ、空白は無視し、ドットを含まない行は読み飛ばす
+    pub fn from_source(text: &str) -> anyhow::Result<Self> {
+        let mut dot_map: Vec<Vec<bool>> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let mut dot_line = Vec::new();
+            for c in line.chars() {
+                match c {
+                    '#' => dot_line.push(true),
+                    '_' => dot_line.push(false),
+                    '"' | ',' => (),
+                    c if c.is_whitespace() => (),
+                    c => bail!("{}行目に使えない文字があります: {:?}", i + 1, c),
+                }
+            }
+            if dot_line.is_empty() {
+                continue;
+            }
+            if let Some(first) = dot_map.first() {
+                ensure!(
+                    first.len() == dot_line.len(),
+                    "{}行目の幅が他の行と異なります",
+                    i + 1
+                );
+            }
+            dot_map.push(dot_line);
+        }
+        ensure!(!dot_map.is_empty(), "ドットがありません");
+        Ok(DotShape {
+            width: dot_map[0].len() as u32,
+            height: dot_map.len() as u32,
+            dot_map,
+        })
     }
 }
 
-// ドットデータを変更する際はこの中身のみ変更する
+// 組み込みのドットマップ(ゲーム内で使う名前のみ渡すため、不明な名前なら停止する)
 pub fn ret_dot_data(name: &str) -> DotShape {
+    find_dot_data(name).unwrap_or_else(|| {
+        panic!(
+            "{}のドットマップ取得に失敗しました。プログラムを終了します。",
+            name
+        )
+    })
+}
+
+// ゲーム中に描くときの拡大率(1ドットあたりのpixel数)
+// 当たり判定の大きさもこの拡大率で決まる
+pub fn display_scale(name: &str) -> f64 {
+    match name {
+        "crab_down" | "crab_banzai" | "octopus_open" | "octopus_close" | "squid_open"
+        | "squid_close" | "explosion" | "ufo" | "ufo_explosion" => 2.3,
+        "player_explosion_1" | "player_explosion_2" | "enemy_bullet_explosion" => 3.,
+        _ => 2.5,
+    }
+}
+
+// ゲーム中に描くときの幅と高さ [pixel]
+pub fn display_size(name: &str) -> (f64, f64) {
+    let shape = ret_dot_data(name);
+    let scale = display_scale(name);
+    (shape.width as f64 * scale, shape.height as f64 * scale)
+}
+
+// ドットデータを変更する際はこの中身のみ変更する
+// 不明な名前ならNoneを返す
+pub fn find_dot_data(name: &str) -> Option<DotShape> {
     let player = DotShape {
         width: 13,
         height: 8,
@@ -305,7 +396,7 @@ pub fn ret_dot_data(name: &str) -> DotShape {
             "_ # _ # _ #",
         ]),
     };
-    match name {
+    let dot_shape = match name {
         "player" => player,
         "player_bullet" => bullet_player,
         "crab_down" => crab_down,
@@ -325,11 +416,9 @@ pub fn ret_dot_data(name: &str) -> DotShape {
         "enemy_bullet_explosion" => enemy_bullet_explosion,
         "player_explosion_1" => player_explosion_1,
         "player_explosion_2" => player_explosion_2,
-        _ => panic!(
-            "{}のドットマップ取得に失敗しました。プログラムを終了します。",
-            name
-        ), // ドットマップ取得失敗
-    }
+        _ => return None, // ドットマップ取得失敗
+    };
+    Some(dot_shape)
 }
 // 描画部分を真、非描画部分を偽とするドットマップを返す
 fn convert_dot_map(dot_map: Vec<&str>) -> Vec<Vec<bool>> {
@@ -349,16 +438,3 @@ fn convert_dot_map(dot_map: Vec<&str>) -> Vec<Vec<bool>> {
     }
     bool_map
 }
-
-// 指定した色に対応するrgbaの値を返す
-pub fn set_color(color: Color) -> Vec<u8> {
-    match color {
-        Color::Turquoise => vec![68, 200, 210, 255],    // 青緑色
-        Color::PlayerBullet => vec![65, 199, 209, 255], // プレイヤーの弾専用色
-        Color::Purple => vec![219, 85, 221, 255],       // 紫色
-        Color::Green => vec![98, 222, 109, 255],        // 緑色
-        Color::Red => vec![210, 0, 0, 255],             // 赤色
-        Color::Yellow => vec![190, 180, 80, 255],       //黄色
-        Color::Background => vec![0, 0, 0, 255],        // 背景色
-    }
-}
@@ -1,267 +1,277 @@
-use std::collections::HashMap;
-use web_sys::CanvasRenderingContext2d;
-use web_sys::ImageBitmap;
-
-use crate::dot_data::Color;
-use crate::draw_background_rect;
-use crate::load_image::ImageType;
+use crate::checksum::Checksum;
+use crate::difficulty::DifficultyConfig;
+use crate::dot_data;
+use crate::game::SoundEvent;
 use crate::math::Vec2;
-use crate::pixel_ctrl;
 use crate::player;
-use crate::sound::Audio;
+use crate::rng::Rng;
+use crate::stage::Stage;
+use crate::torchika::{self, Torchika};
+use serde::{Deserialize, Serialize};
 
-#[derive(Eq, Hash, PartialEq)]
-enum EnemyType {
+#[derive(Debug, Eq, Hash, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnemyType {
     Octopus,
     Crab,
     Squid,
 }
-struct Bullet {
-    width: f64,    // 描画サイズの幅 [pixel]
-    height: f64,   // 描画サイズの高さ [pixel]
-    pos: Vec2,     // 移動後の中心位置
-    pre_pos: Vec2, // 前回描画時の中心位置
-    live: bool,    // 弾が画面中に存在しているか否か
-    image: ImageBitmap,
-    explosion: BulletExplosion,
+// 敵弾の種類
+#[derive(Eq, Hash, PartialEq, Clone, Copy)]
+pub enum BulletType {
+    Plunger,  // T字型
+    Squiggly, // ジグザク型
+    Rolling,  // 螺旋状
+}
+pub struct Bullet {
+    pub bullet_type: BulletType,
+    pub width: f64,  // 描画サイズの幅 [pixel]
+    pub height: f64, // 描画サイズの高さ [pixel]
+    pub pos: Vec2,   // 移動後の中心位置
+    pub live: bool,  // 弾が画面中に存在しているか否か
+    pub explosion: BulletExplosion,
+    speed: f64, // 1フレームの移動距離 [pixel]
 }
 
 impl Bullet {
+    fn new(bullet_type: BulletType, speed: f64) -> Self {
+        let image_name = match bullet_type {
+            BulletType::Plunger => "enemy_bullet_plunger",
+            BulletType::Squiggly => "enemy_bullet_squiggly",
+            BulletType::Rolling => "enemy_bullet_rolling",
+        };
+        let (width, height) = dot_data::display_size(image_name);
+        let (width_explosion, height_explosion) = dot_data::display_size("enemy_bullet_explosion");
+        Bullet {
+            bullet_type,
+            width,
+            height,
+            pos: Vec2::new(0., 0.),
+            live: false,
+            explosion: BulletExplosion {
+                width: width_explosion,
+                height: height_explosion,
+                pos: Vec2::new(0., 0.),
+                effect_cnt: None,
+            },
+            speed,
+        }
+    }
     // 弾を指定された場所から発射
     fn set(&mut self, pos: Vec2) {
         // 小数点以下を0にしておく
         self.pos = pos.round();
-        self.pre_pos = self.pos;
         self.live = true;
     }
+    // 弾を消す
+    fn vanish(&mut self) {
+        self.live = false;
+    }
+    // 弾を消して着弾エフェクトを表示する
+    fn explode(&mut self) {
+        self.vanish();
+        self.explosion.pos = self.pos;
+        self.explosion.effect_cnt = Some(20);
+    }
+    // 障害物との当たり判定に使う、弾の左右の座標
+    fn probe_pos(&self) -> [Vec2; 2] {
+        [
+            Vec2::new(self.pos.x - self.width / 2. - 2., self.pos.y),
+            Vec2::new(self.pos.x + self.width / 2. + 2., self.pos.y),
+        ]
+    }
 
     fn update(
         &mut self,
-        ctx: &CanvasRenderingContext2d,
-        canvas_width: f64,
         canvas_height: f64,
-        player: &mut player::Player,
+        players: &mut [&mut player::Player],
+        torchika_list: &mut [Torchika],
     ) {
+        // 着弾エフェクトを表示するか
+        if let Some(cnt) = self.explosion.effect_cnt {
+            //一定時間は表示
+            if cnt > 0 {
+                self.explosion.effect_cnt = Some(cnt - 1);
+            } else {
+                //一定時間経過後は削除
+                self.explosion.effect_cnt = None;
+            }
+        }
         if !self.live {
             return;
         }
         //弾が存在していたら移動する
-        self.pos.y += 3.;
+        self.pos.y += self.speed;
         // 赤線の当たりに着弾した場合
         if self.pos.y > canvas_height - 52. {
-            // 弾を消す
-            self.live = false;
-            draw_background_rect(
-                ctx,
-                self.pre_pos.x - self.width / 2.,
-                self.pre_pos.y - self.height / 2.,
-                self.width,
-                self.height,
-            );
-            self.explosion.pos = self.pos;
-            self.explosion.effect_cnt = Some(20);
+            self.explode();
             return;
         }
-        //プレイヤーと衝突した場合
-        if Vec2::new(self.pos.x, self.pos.y - self.height / 2.).collision(
-            &player.pos,
-            player.width,
-            player.height,
-        ) {
-            //撃破後の状態でなければ
-            if player.break_cnt == None {
-                //プレイヤーを消す
-                player.break_cnt = Some(player.revival_set_cnt);
-                // プレイヤーの残機を減らす
-                player.life -= 1;
-                // 弾を消す
-                self.live = false;
-                draw_background_rect(
-                    ctx,
-                    self.pre_pos.x - self.width / 2.,
-                    self.pre_pos.y - self.height / 2.,
-                    self.width,
-                    self.height,
-                );
+        for player in players.iter_mut() {
+            // 残機が無くなったプレイヤーには当たらない
+            if player.is_out() {
+                continue;
+            }
+            //プレイヤーと衝突した場合
+            if Vec2::new(self.pos.x, self.pos.y - self.height / 2.).collision(
+                &player.pos,
+                player.width,
+                player.height,
+            ) {
+                //撃破後の状態でなければ
+                if player.break_cnt.is_none() {
+                    //プレイヤーを消す
+                    player.break_cnt = Some(player.revival_set_cnt);
+                    // プレイヤーの残機を減らす
+                    player.life -= 1;
+                    // 弾を消す
+                    self.vanish();
+                }
+                return;
             }
-            return;
         }
 
-        // トーチカまたはプレイヤーの弾への着弾確認
-        // とりあえず弾の周りのデータまであれば十分
-        let left_pos = Vec2::new(self.pos.x - self.width / 2. - 2., self.pos.y);
-        let right_pos = Vec2::new(self.pos.x + self.width / 2. + 2., self.pos.y);
-        let collision = pixel_ctrl::detect_pixel_diff(
-            canvas_width,
-            vec![left_pos, right_pos],
-            vec![Color::PlayerBullet, Color::Red],
-            ctx.get_image_data(0., 0., canvas_width, self.pos.y + self.height)
-                .unwrap(),
-        );
-        if collision {
-            // 弾を消す
-            self.live = false;
-            draw_background_rect(
-                ctx,
-                self.pre_pos.x - self.width / 2.,
-                self.pre_pos.y - self.height / 2.,
-                self.width,
-                self.height,
-            );
-            self.explosion.pos = self.pos;
-            self.explosion.effect_cnt = Some(20);
-        }
-    }
-    fn render(&mut self, ctx: &CanvasRenderingContext2d) {
-        if self.live {
-            draw_background_rect(
-                ctx,
-                self.pre_pos.x - self.width / 2.,
-                self.pre_pos.y - self.height / 2.,
-                self.width,
-                self.height,
-            );
-            // 表画像
-            ctx.draw_image_with_image_bitmap_and_dw_and_dh(
-                &self.image,
-                self.pos.x - self.width / 2.,
-                self.pos.y - self.height / 2.,
-                self.width,
-                self.height,
-            )
-            .unwrap();
-            self.pre_pos = self.pos;
-        }
-        if let Some(cnt) = self.explosion.effect_cnt {
-            //一定時間は表示
-            if cnt > 0 {
-                ctx.draw_image_with_image_bitmap_and_dw_and_dh(
-                    self.explosion.image_front.as_ref().unwrap(),
-                    self.explosion.pos.x - self.explosion.width / 2.,
-                    self.explosion.pos.y - self.explosion.height / 2.,
-                    self.explosion.width,
-                    self.explosion.height,
-                )
-                .unwrap();
-                self.explosion.effect_cnt = Some(cnt - 1);
-            } else {
-                //一定時間経過後は削除
-                ctx.draw_image_with_image_bitmap_and_dw_and_dh(
-                    self.explosion.image_shadow.as_ref().unwrap(),
-                    self.explosion.pos.x - self.explosion.width / 2.,
-                    self.explosion.pos.y - self.explosion.height / 2.,
-                    self.explosion.width,
-                    self.explosion.height,
+        // プレイヤーの弾への着弾確認
+        for player in players.iter_mut() {
+            let player_bullet = &mut player.bullet;
+            if !player_bullet.live {
+                continue;
+            }
+            if self.probe_pos().iter().any(|pos| {
+                pos.collision(
+                    &player_bullet.pos,
+                    player_bullet.width,
+                    player_bullet.height,
                 )
-                .unwrap();
-                self.explosion.effect_cnt = None;
+            }) {
+                self.explode();
+                return;
+            }
+            // プレイヤーの弾から見て敵の弾に触れていた場合
+            if player_bullet
+                .probe_pos()
+                .iter()
+                .any(|pos| pos.collision(&self.pos, self.width, self.height))
+            {
+                player_bullet.land_obstacle();
             }
         }
+        // トーチカへの着弾確認
+        if torchika::collision(torchika_list, &self.probe_pos()) {
+            self.explode();
+            // 着弾エフェクトの形にトーチカを削る
+            torchika::erode_with_shape(
+                torchika_list,
+                self.explosion.pos,
+                &dot_data::ret_dot_data("enemy_bullet_explosion"),
+                dot_data::display_scale("enemy_bullet_explosion"),
+            );
+        }
+    }
+    fn write_checksum(&self, checksum: &mut Checksum) {
+        checksum.write_vec2(&self.pos);
+        checksum.write_bool(self.live);
+        checksum.write_vec2(&self.explosion.pos);
+        checksum.write_option_i32(self.explosion.effect_cnt);
     }
 }
-struct BulletExplosion {
-    width: f64,
-    height: f64,
-    pos: Vec2,
-    effect_cnt: Option<i32>,           //エフェクト表示中はSome(カウント)
-    image_front: Option<ImageBitmap>,  // 着弾時の表画像
-    image_shadow: Option<ImageBitmap>, // 着弾時の影画像
+pub struct BulletExplosion {
+    pub width: f64,
+    pub height: f64,
+    pub pos: Vec2,
+    pub effect_cnt: Option<i32>, //エフェクト表示中はSome(カウント)
 }
 
-struct Explosion {
-    // 爆発エフェクト表示中は
-    show: Option<ImageBitmap>,
-    pos: Vec2,
+pub struct Explosion {
+    // 爆発エフェクト表示中は撃破された敵の種類
+    pub show: Option<EnemyType>,
+    pub pos: Vec2,
     // 表示カウント(0になったら消滅)
     count: i32,
     // 表示幅
-    width: f64,
+    pub width: f64,
     // 表示高さ
-    height: f64,
-    enemy_type_map: HashMap<EnemyType, ImageBitmap>,
+    pub height: f64,
 }
 impl Explosion {
-    fn create_effect(&mut self, pos: Vec2, image: ImageBitmap) {
-        self.show = Some(image);
+    fn create_effect(&mut self, pos: Vec2, enemy_type: EnemyType) {
+        self.show = Some(enemy_type);
         self.pos = pos;
         self.count = 15;
     }
-    fn update_render(
-        &mut self,
-        ctx: &CanvasRenderingContext2d,
-        player_bullet: &mut player::Bullet,
-    ) {
+    fn update(&mut self, players: &mut [&mut player::Player]) {
         // エフェクト表示中であれば
-        if let Some(image) = self.show.as_ref() {
-            ctx.draw_image_with_image_bitmap_and_dw_and_dh(
-                image,
-                self.pos.x - self.width / 2.,
-                self.pos.y - self.height / 2.,
-                self.width,
-                self.height,
-            )
-            .unwrap();
+        if self.show.is_some() {
             self.count -= 1;
         }
         // 一定フレーム経過したら
         if self.count < 0 {
             // 削除
             self.show = None;
-            // 爆発エフェクトを消す
-            draw_background_rect(
-                ctx,
-                self.pos.x - self.width / 2.,
-                self.pos.y - self.height / 2.,
-                self.width,
-                self.height,
-            );
             // 爆発エフェクトが消えてからプレイヤーの射撃を可能とする
-            player_bullet.can_shot = true;
+            // (2人同時プレイで同時に撃破した場合は、敵を撃破した両方の弾)
+            for player in players.iter_mut() {
+                let bullet = &mut player.bullet;
+                if !bullet.live && bullet.land_effect_cnt.is_none() {
+                    bullet.can_shot = true;
+                }
+            }
         }
     }
 }
 
-struct Enemy {
-    enemy_type: EnemyType,
-    width: f64,            // 描画サイズの幅 [pixel]
-    height: f64,           // 描画サイズの高さ [pixel]
-    pos: Vec2,             // 移動後の中心位置
-    pre_pos: Vec2,         // 前回描画時の中心位置
-    move_turn: bool,       // 動く順番がきたら真
-    live: bool,            // 生死
-    remove: bool,          // 削除時に残った描画処理の必用がある場合真
-    show_image_type: bool, // どちらの状態の画像を表示するか
-    // 表画像
-    image_type1_front: ImageBitmap,
-    image_type2_front: ImageBitmap,
+pub struct Enemy {
+    pub enemy_type: EnemyType,
+    pub width: f64,            // 描画サイズの幅 [pixel]
+    pub height: f64,           // 描画サイズの高さ [pixel]
+    pub pos: Vec2,             // 移動後の中心位置
+    move_turn: bool,           // 動く順番がきたら真
+    pub live: bool,            // 生死
+    pub show_image_type: bool, // どちらの状態の画像を表示するか
 }
 
 impl Enemy {
+    fn new(enemy_type: EnemyType) -> Self {
+        let image_name = match enemy_type {
+            EnemyType::Octopus => "octopus_open",
+            EnemyType::Crab => "crab_banzai",
+            EnemyType::Squid => "squid_open",
+        };
+        let (width, height) = dot_data::display_size(image_name);
+        Enemy {
+            enemy_type,
+            width,
+            height,
+            pos: Vec2::new(0., 0.),
+            move_turn: false,
+            live: true,
+            show_image_type: true,
+        }
+    }
+    // movementは動く順番がきた時の移動量
     fn update(
         &mut self,
-        move_dir: i32,
-        move_down: bool,
-        player_bullet: &mut player::Bullet,
+        movement: Vec2,
+        player_bullets: &mut [&mut player::Bullet],
         explosion: &mut Explosion,
-        audio: &Audio,
+        torchika_list: &mut [Torchika],
+        sounds: &mut Vec<SoundEvent>,
     ) {
         if !self.live {
             // 死んでいたら何もしない
             return;
         }
-        // プレイヤーの弾が画面上に存在して
-        if player_bullet.live {
-            // 弾と衝突していた場合
-            if player_bullet
-                .pos
-                .collision(&self.pos, self.width, self.height)
+        for player_bullet in player_bullets.iter_mut() {
+            // プレイヤーの弾が画面上に存在して、弾と衝突していた場合
+            if player_bullet.live
+                && player_bullet
+                    .pos
+                    .collision(&self.pos, self.width, self.height)
             {
                 // 自分を削除
                 self.live = false;
-                self.remove = true;
                 // プレイヤーの弾を消す
                 player_bullet.live = false;
-                player_bullet.remove = Some(player_bullet.pre_pos);
                 //点数を追加
                 player_bullet.score.sum += {
                     match self.enemy_type {
@@ -271,75 +281,28 @@ impl Enemy {
                     }
                 };
                 // 爆発エフェクトを生成
-                explosion.create_effect(
-                    self.pos,
-                    explosion
-                        .enemy_type_map
-                        .get(&self.enemy_type)
-                        .unwrap()
-                        .clone(),
-                );
+                explosion.create_effect(self.pos, self.enemy_type);
                 // インベーダー撃破音再生
-                if let Some(sound) = &audio.invader_explosion {
-                    audio.play_once_sound(sound);
-                }
+                sounds.push(SoundEvent::InvaderExplosion);
+                break;
             }
         }
         // 動く時
         if self.move_turn {
             // 方向を考慮して動く
-            self.pos.x += 7. * move_dir as f64;
-            if move_down {
-                self.pos.y += 8. * 2.3;
-            }
+            self.pos.x += movement.x;
+            self.pos.y += movement.y;
             // 表示する画像を切り替える
-            self.show_image_type = !self.show_image_type
+            self.show_image_type = !self.show_image_type;
+            // 重なったトーチカを削る
+            torchika::erode_with_rect(torchika_list, self.pos, self.width, self.height);
         }
     }
-
-    fn render(&mut self, ctx: &CanvasRenderingContext2d) {
-        // 削除処理
-        if self.remove {
-            // 影画像(前回の部分を消す)
-            draw_background_rect(
-                ctx,
-                self.pre_pos.x - self.width / 2.,
-                self.pre_pos.y - self.height / 2.,
-                self.width,
-                self.height,
-            );
-            // 削除処理完了
-            self.remove = false;
-        }
-
-        // 死んでいる場合は描画しない
-        if !self.live {
-            return;
-        }
-        // 表示画像選択
-        let show_image_front = if self.show_image_type {
-            &self.image_type1_front
-        } else {
-            &self.image_type2_front
-        };
-        // 影画像(前回の部分を消す)
-        draw_background_rect(
-            ctx,
-            self.pre_pos.x - self.width / 2.,
-            self.pre_pos.y - self.height / 2.,
-            self.width,
-            self.height,
-        );
-        // 表画像
-        ctx.draw_image_with_image_bitmap_and_dw_and_dh(
-            show_image_front,
-            self.pos.x - self.width / 2.,
-            self.pos.y - self.height / 2.,
-            self.width,
-            self.height,
-        )
-        .unwrap();
-        self.pre_pos = self.pos;
+    fn write_checksum(&self, checksum: &mut Checksum) {
+        checksum.write_vec2(&self.pos);
+        checksum.write_bool(self.move_turn);
+        checksum.write_bool(self.live);
+        checksum.write_bool(self.show_image_type);
     }
 }
 
@@ -353,259 +316,108 @@ pub struct EnemyManage {
     move_dir_invert: bool,
     // 移動方向反転時
     move_down: bool,
-    // 種類に対応した画像を保存
-    pub images_list: HashMap<ImageType, ImageBitmap>,
     // 敵一覧
-    enemys_list: Vec<Enemy>,
+    pub enemys_list: Vec<Enemy>,
     // 爆発エフェクト
-    explosion: Explosion,
+    pub explosion: Explosion,
     // 敵弾3種類
-    bullets: Vec<Bullet>,
+    pub bullets: Vec<Bullet>,
     //敵の各縦列の中で一番下(射撃可能)の個体のインデックス番号
     can_shot_enemy: Vec<usize>,
+    // 1段に並ぶ個体の数
+    columns: usize,
     //射撃してからのフレーム数
     shot_interval: usize,
+    // 前回の射撃から次に射撃できるまでのフレーム数
+    shot_wait: usize,
     // 前回再生した音番号
     play_sound_index: usize,
-    canvas_width: f64,
     canvas_height: f64,
+    config: DifficultyConfig,
 }
 
 impl EnemyManage {
-    pub fn default() -> Self {
+    // インベーダーはresetでステージの定義から並べる
+    pub fn new(canvas_width: f64, canvas_height: f64, config: &DifficultyConfig) -> Self {
+        let (width_explosion, height_explosion) = dot_data::display_size("explosion");
         EnemyManage {
             left_border: 35.,
-            right_border: 505.,
+            right_border: canvas_width - 35.,
             move_dir: 1,
             move_dir_invert: false,
             move_down: false,
-            images_list: HashMap::new(),
             enemys_list: Vec::new(),
             explosion: Explosion {
                 show: None,
                 pos: Vec2 { x: 0., y: 0. },
                 count: 0,
-                width: 0.,
-                height: 0.,
-                enemy_type_map: HashMap::new(),
+                width: width_explosion,
+                height: height_explosion,
             },
-            bullets: Vec::new(),
-            can_shot_enemy: core::array::from_fn::<usize, 11, _>(|i| i).to_vec(),
+            bullets: vec![
+                Bullet::new(BulletType::Plunger, config.enemy_bullet_speed),
+                Bullet::new(BulletType::Squiggly, config.enemy_bullet_speed),
+                Bullet::new(BulletType::Rolling, config.enemy_bullet_speed),
+            ],
+            can_shot_enemy: Vec::new(),
+            columns: 0,
             shot_interval: 0,
+            shot_wait: config.enemy_shot_interval,
             play_sound_index: 0,
-            canvas_width: 0.,
-            canvas_height: 0.,
-        }
-    }
-    pub fn register_enemys(&mut self, canvas_width: f64, canvas_height: f64) {
-        self.canvas_width = canvas_width;
-        self.canvas_height = canvas_height;
-        let image_type1_front = self.images_list.get(&ImageType::OctopusOpen).unwrap();
-        let image_type2_front = self.images_list.get(&ImageType::OctopusClose).unwrap();
-        let invader_column = 11;
-        // 表示サイズ/オリジナルの画像サイズ
-        let scale = 2.3;
-        let invader_pos = Vec2::new(0., 0.);
-        for _ in 0..2 {
-            for _ in 0..invader_column {
-                self.enemys_list.push(Enemy {
-                    enemy_type: EnemyType::Octopus,
-                    width: image_type1_front.width() as f64 * scale,
-                    height: image_type1_front.height() as f64 * scale,
-                    pos: invader_pos,
-                    pre_pos: invader_pos,
-                    move_turn: false,
-                    live: true,
-                    remove: false,
-                    show_image_type: true,
-                    image_type1_front: image_type1_front.clone(),
-                    image_type2_front: image_type2_front.clone(),
-                });
-            }
-        }
-
-        let image_type1_front = self.images_list.get(&ImageType::CrabBanzai).unwrap();
-        let image_type2_front = self.images_list.get(&ImageType::CrabDown).unwrap();
-
-        for _ in 0..2 {
-            for _ in 0..invader_column {
-                self.enemys_list.push(Enemy {
-                    enemy_type: EnemyType::Crab,
-                    width: image_type1_front.width() as f64 * scale,
-                    height: image_type1_front.height() as f64 * scale,
-                    pos: invader_pos,
-                    pre_pos: invader_pos,
-                    move_turn: false,
-                    live: true,
-                    remove: false,
-                    show_image_type: true,
-                    image_type1_front: image_type1_front.clone(),
-                    image_type2_front: image_type2_front.clone(),
-                });
-            }
-        }
-        let image_type1_front = self.images_list.get(&ImageType::SquidOpen).unwrap();
-        let image_type2_front = self.images_list.get(&ImageType::SquidClose).unwrap();
-
-        for _ in 0..invader_column {
-            self.enemys_list.push(Enemy {
-                enemy_type: EnemyType::Squid,
-                width: image_type1_front.width() as f64 * scale,
-                height: image_type1_front.height() as f64 * scale,
-                pos: invader_pos,
-                pre_pos: invader_pos,
-                move_turn: false,
-                live: true,
-                remove: false,
-                show_image_type: true,
-                image_type1_front: image_type1_front.clone(),
-                image_type2_front: image_type2_front.clone(),
-            });
+            canvas_height,
+            config: *config,
         }
-
-        self.enemys_list[0].move_turn = true;
-        // 爆発画像を登録
-        let explosion_turquoise = self
-            .images_list
-            .get(&ImageType::ExplosionTurquoise)
-            .unwrap();
-        let explosion_purple = self.images_list.get(&ImageType::ExplosionPurple).unwrap();
-        let explosion_green = self.images_list.get(&ImageType::ExpolsionGreen).unwrap();
-        let mut explosion_image = HashMap::new();
-        explosion_image.insert(EnemyType::Octopus, explosion_purple.clone());
-        explosion_image.insert(EnemyType::Crab, explosion_turquoise.clone());
-        explosion_image.insert(EnemyType::Squid, explosion_green.clone());
-        self.explosion = Explosion {
-            show: None,
-            width: explosion_turquoise.width() as f64 * scale,
-            height: explosion_turquoise.height() as f64 * scale,
-            enemy_type_map: explosion_image,
-            ..self.explosion
-        };
-
-        let image = self
-            .images_list
-            .get(&ImageType::EnemyBulletPlunger)
-            .unwrap();
-        let image_explosion_front = self
-            .images_list
-            .get(&ImageType::EnemyBulletExplosionFront)
-            .unwrap();
-        let image_explosion_shadow = self
-            .images_list
-            .get(&ImageType::EnemyBulletExplosionShadow)
-            .unwrap();
-        let bullet = Bullet {
-            width: image.width() as f64 * 2.5,
-            height: image.height() as f64 * 2.5,
-            pos: Vec2::new(0., 0.),
-            pre_pos: Vec2::new(0., 0.),
-            live: false,
-            image: image.clone(),
-            explosion: BulletExplosion {
-                width: image_explosion_front.width() as f64 * 3.,
-                height: image_explosion_front.height() as f64 * 3.,
-                pos: Vec2::new(0., 0.),
-                effect_cnt: None,
-                image_front: Some(image_explosion_front.clone()),
-                image_shadow: Some(image_explosion_shadow.clone()),
-            },
-        };
-        self.bullets.push(bullet);
-
-        let image = self
-            .images_list
-            .get(&ImageType::EnemyBulletSquiggly)
-            .unwrap();
-        let image_explosion_front = self
-            .images_list
-            .get(&ImageType::EnemyBulletExplosionFront)
-            .unwrap();
-        let image_explosion_shadow = self
-            .images_list
-            .get(&ImageType::EnemyBulletExplosionShadow)
-            .unwrap();
-        let bullet = Bullet {
-            width: image.width() as f64 * 2.5,
-            height: image.height() as f64 * 2.5,
-            pos: Vec2::new(0., 0.),
-            pre_pos: Vec2::new(0., 0.),
-            live: false,
-            image: image.clone(),
-            explosion: BulletExplosion {
-                width: image_explosion_front.width() as f64 * 3.,
-                height: image_explosion_front.height() as f64 * 3.,
-                pos: Vec2::new(0., 0.),
-                effect_cnt: None,
-                image_front: Some(image_explosion_front.clone()),
-                image_shadow: Some(image_explosion_shadow.clone()),
-            },
-        };
-        self.bullets.push(bullet);
-
-        let image = self
-            .images_list
-            .get(&ImageType::EnemyBulletRolling)
-            .unwrap();
-        let image_explosion_front = self
-            .images_list
-            .get(&ImageType::EnemyBulletExplosionFront)
-            .unwrap();
-        let image_explosion_shadow = self
-            .images_list
-            .get(&ImageType::EnemyBulletExplosionShadow)
-            .unwrap();
-        let bullet = Bullet {
-            width: image.width() as f64 * 2.5,
-            height: image.height() as f64 * 2.5,
-            pos: Vec2::new(0., 0.),
-            pre_pos: Vec2::new(0., 0.),
-            live: false,
-            image: image.clone(),
-            explosion: BulletExplosion {
-                width: image_explosion_front.width() as f64 * 3.,
-                height: image_explosion_front.height() as f64 * 3.,
-                pos: Vec2::new(0., 0.),
-                effect_cnt: None,
-                image_front: Some(image_explosion_front.clone()),
-                image_shadow: Some(image_explosion_shadow.clone()),
-            },
-        };
-        self.bullets.push(bullet);
     }
     pub fn update(
         &mut self,
-        ctx: &CanvasRenderingContext2d,
-        player: &mut player::Player,
-        audio: &Audio,
+        players: &mut [&mut player::Player],
+        torchika_list: &mut [Torchika],
+        rng: &mut Rng,
+        sounds: &mut Vec<SoundEvent>,
     ) {
-        if let Some(_) = self.explosion.show {
+        if self.explosion.show.is_some() {
             // 爆発エフェクト表示
-            self.explosion.update_render(ctx, &mut player.bullet);
+            self.explosion.update(players);
             // 既に発射した敵弾は動かす
             for bullet in &mut self.bullets {
-                bullet.update(ctx, self.canvas_width, self.canvas_height, player);
+                bullet.update(self.canvas_height, players, torchika_list);
             }
             // 爆発エフェクト表示中は敵の動きをすべて止める
             return;
         }
         // プレイヤーが爆発中は、インベーダーの動きをすべて止める
-        if let Some(_) = player.break_cnt {
+        if players
+            .iter()
+            .any(|player| player.break_cnt.is_some() && !player.is_out())
+        {
             // 既に発射した敵弾は動かす
             for bullet in &mut self.bullets {
-                bullet.update(ctx, self.canvas_width, self.canvas_height, player);
+                bullet.update(self.canvas_height, players, torchika_list);
             }
             return;
         }
 
         // 各敵個体の移動処理
+        let mut player_bullets: Vec<&mut player::Bullet> = players
+            .iter_mut()
+            .map(|player| &mut player.bullet)
+            .collect();
+        // 方向を考慮した移動量(移動方向反転時は下へも移動する)
+        let movement = Vec2::new(
+            self.config.invader_step * self.move_dir as f64,
+            if self.move_down {
+                self.config.invader_drop
+            } else {
+                0.
+            },
+        );
         self.enemys_list.iter_mut().for_each(|enemy| {
             enemy.update(
-                self.move_dir,
-                self.move_down,
-                &mut player.bullet,
+                movement,
+                &mut player_bullets,
                 &mut self.explosion,
-                audio,
+                torchika_list,
+                sounds,
             );
         });
 
@@ -635,7 +447,7 @@ impl EnemyManage {
             }
         }
         // 動いた個体より後がすべて死んでいた場合
-        if next_move_enemy_index == None {
+        if next_move_enemy_index.is_none() {
             // 移動方向反転フラグが立っている場合
             if self.move_dir_invert {
                 // 移動方向を反転
@@ -655,17 +467,9 @@ impl EnemyManage {
                     break;
                 }
             }
-            // サウンドが保存されていれば
-            if audio.invader_move.len() > 0 {
-                // 順番にループ
-                self.play_sound_index = if self.play_sound_index >= audio.invader_move.len() {
-                    0
-                } else {
-                    self.play_sound_index
-                };
-                audio.play_once_sound(&audio.invader_move[self.play_sound_index]);
-                self.play_sound_index += 1;
-            }
+            // 4種類の移動音を順番にループ
+            sounds.push(SoundEvent::InvaderMove(self.play_sound_index));
+            self.play_sound_index = (self.play_sound_index + 1) % 4;
         }
         if let Some(i) = next_move_enemy_index {
             // 次に動く敵個体を指定
@@ -678,49 +482,49 @@ impl EnemyManage {
 
         for bullet in &mut self.bullets {
             // 敵が全滅していたら発射しない
-            if self.can_shot_enemy.len() == 0 {
+            if self.can_shot_enemy.is_empty() {
                 return;
             }
             //弾が消滅済みで、かつ前回の射撃から(3発の弾共通で)一定時間経過して、かつ弾の爆発エフェクト表示が終了していた場合
-            if !bullet.live && self.shot_interval > 70 && bullet.explosion.effect_cnt == None {
+            if !bullet.live
+                && self.shot_interval > self.shot_wait
+                && bullet.explosion.effect_cnt.is_none()
+            {
                 // プレイヤーに一番近い敵個体の番号を求める
+                // (2人同時プレイでは、残機のあるどちらかのプレイヤーに一番近い敵)
+                let distance = |x: f64| {
+                    players
+                        .iter()
+                        .filter(|player| !player.is_out())
+                        .map(|player| (x - player.pos.x).abs())
+                        .fold(f64::INFINITY, f64::min)
+                };
                 let mut i_near_enemy = self.can_shot_enemy[0];
                 for i in &self.can_shot_enemy {
-                    if (self.enemys_list[*i].pos.x - player.pos.x).abs()
-                        < (self.enemys_list[i_near_enemy].pos.x - player.pos.x).abs()
+                    if distance(self.enemys_list[*i].pos.x)
+                        < distance(self.enemys_list[i_near_enemy].pos.x)
                     {
                         i_near_enemy = *i;
                     }
                 }
-                //疑似的な乱数で発射タイミングを決定
-                let i_shot_enemy;
-                let seed = (player.pos.x + self.enemys_list[self.can_shot_enemy[0]].pos.x) as usize;
-                if seed % 3 == 0 {
+                //乱数で射撃する敵個体を決定
+                let i_shot_enemy = if rng.one_in(3) {
                     // 確率1/3でプレイヤーに一番近い敵が射撃する
-                    i_shot_enemy = i_near_enemy;
+                    i_near_enemy
                 } else {
                     // 確率2/3でランダムな列から射撃
-                    i_shot_enemy = self.can_shot_enemy[seed % self.can_shot_enemy.len()];
-                }
+                    self.can_shot_enemy[rng.gen_range(self.can_shot_enemy.len())]
+                };
                 bullet.set(Vec2::new(
                     self.enemys_list[i_shot_enemy].pos.x,
                     self.enemys_list[i_shot_enemy].pos.y + 40.,
                 ));
                 self.shot_interval = 0;
             }
-            bullet.update(ctx, self.canvas_width, self.canvas_height, player);
+            bullet.update(self.canvas_height, players, torchika_list);
         }
         self.shot_interval += 1;
     }
-    pub fn render(&mut self, ctx: &CanvasRenderingContext2d) {
-        self.enemys_list.iter_mut().for_each(|enemy| {
-            enemy.render(ctx);
-        });
-        //弾
-        for b in &mut self.bullets {
-            b.render(ctx);
-        }
-    }
     //各縦列で射撃可能な個体の情報を更新
     fn update_can_shot_list(&mut self) {
         // 各縦列について
@@ -728,7 +532,7 @@ impl EnemyManage {
             //登録されている個体が死んでいた場合
             while !self.enemys_list[self.can_shot_enemy[i]].live {
                 //一段上の個体を登録
-                self.can_shot_enemy[i] += 11;
+                self.can_shot_enemy[i] += self.columns;
                 //はみだしていたら、その縦一列は全滅状態
                 if self.can_shot_enemy[i] >= self.enemys_list.len() {
                     break;
@@ -738,78 +542,73 @@ impl EnemyManage {
         //はみだした部分(全滅した縦列)を消す
         self.can_shot_enemy.retain(|x| x < &self.enemys_list.len());
     }
+    // 画面に残った弾と着弾エフェクトを消す
+    pub fn clear_bullets(&mut self) {
+        for bullet in self.bullets.iter_mut() {
+            if bullet.live {
+                bullet.vanish();
+            }
+            bullet.explosion.effect_cnt = None;
+        }
+    }
     // 敵の弾の発射を防ぎたい時などに使う
     pub fn set_shot_interval(&mut self, shot_interval: usize) {
         self.shot_interval = shot_interval;
     }
-    // インベーダーを全て初期化
-    pub fn reset(&mut self, ctx: &CanvasRenderingContext2d, stage_number: usize) {
-        let invader_column = 11;
+    // インベーダーをステージの定義どおりに並べ直す
+    pub fn reset(&mut self, stage: &Stage) {
         // 表示サイズ/オリジナルの画像サイズ
-        let scale = 2.3;
+        let scale = dot_data::display_scale("octopus_open");
         // 各個体の中心座標同士の間隔
         let gap_x = 36.;
         let gap_y = 8. * scale * 2.;
         let init_x = 60.;
-        // ステージ1から9までの最下層個体の初期位置とトーチカの間隔
-        let distance_tochika_per_stage = [7, 4, 2, 1, 1, 1, 0, 0, 0];
         // ステージが進むほど開始位置が下になる(一番低いときはトーチカに触れる位置)
         let mut invader_pos = Vec2::new(
             init_x,
-            self.canvas_height
-                - 180.
-                - 8. * scale * (distance_tochika_per_stage[stage_number - 1] as f64 + 0.5),
+            self.canvas_height - 180. - 8. * scale * (stage.start_height as f64 + 0.5),
         );
-        for row in 0..5 {
-            for column in 0..invader_column {
-                let i = invader_column * row + column;
-                self.enemys_list[i].pos = invader_pos;
-                self.enemys_list[i].pre_pos = invader_pos;
-                self.enemys_list[i].move_turn = false;
-                self.enemys_list[i].live = true;
-                self.enemys_list[i].show_image_type = true;
+        // 一番下の段から順に登録する
+        self.enemys_list.clear();
+        for row in stage.rows.iter().rev() {
+            for column in 0..stage.columns {
+                // 空きマスは、縦列の並びを揃えるため最初から倒された個体として置く
+                let cell = row.cell(column);
+                let mut enemy = Enemy::new(cell.unwrap_or(EnemyType::Octopus));
+                enemy.pos = invader_pos;
+                enemy.live = cell.is_some();
+                self.enemys_list.push(enemy);
 
                 invader_pos.x += gap_x;
             }
             invader_pos.x = init_x;
             invader_pos.y -= gap_y;
         }
-        self.enemys_list[0].move_turn = true;
+        // 最初に動くのは一番下の段の左端にいる個体
+        if let Some(enemy) = self.enemys_list.iter_mut().find(|enemy| enemy.live) {
+            enemy.move_turn = true;
+        }
         self.move_dir = 1;
         self.move_dir_invert = false;
         self.move_down = false;
 
-        // 最後に残った爆発エフェクトを消す
-        draw_background_rect(
-            ctx,
-            self.explosion.pos.x - self.explosion.width / 2.,
-            self.explosion.pos.y - self.explosion.height / 2.,
-            self.explosion.width,
-            self.explosion.height,
-        );
-
         for bullet in self.bullets.iter_mut() {
             // 弾が画面に残っていたら消す
-            if bullet.live {
-                draw_background_rect(
-                    ctx,
-                    bullet.pre_pos.x - bullet.width / 2.,
-                    bullet.pre_pos.y - bullet.height / 2.,
-                    bullet.width,
-                    bullet.height,
-                );
-                bullet.live = false;
-            }
+            bullet.live = false;
         }
 
         self.explosion.count = 0;
-        self.can_shot_enemy = core::array::from_fn::<usize, 11, _>(|i| i).to_vec();
+        self.columns = stage.columns;
+        self.can_shot_enemy = (0..stage.columns).collect();
         self.shot_interval = 0;
+        // 射撃の頻度が高いステージほど間隔を短くする
+        self.shot_wait =
+            (self.config.enemy_shot_interval as f64 / stage.fire_rate).round() as usize;
         self.play_sound_index = 0;
     }
     // 一番下の個体のy座標を、全滅していたら偽を返す
     pub fn nadir_y(&self) -> Option<f64> {
-        if self.can_shot_enemy.len() <= 0 {
+        if self.can_shot_enemy.is_empty() {
             return None;
         }
         // 一番下の個体のy座標
@@ -821,4 +620,25 @@ impl EnemyManage {
         }
         Some(nadir_y)
     }
+    // 状態のハッシュ値を計算する
+    pub fn write_checksum(&self, checksum: &mut Checksum) {
+        checksum.write_i32(self.move_dir);
+        checksum.write_bool(self.move_dir_invert);
+        checksum.write_bool(self.move_down);
+        for enemy in &self.enemys_list {
+            enemy.write_checksum(checksum);
+        }
+        checksum.write_bool(self.explosion.show.is_some());
+        checksum.write_vec2(&self.explosion.pos);
+        checksum.write_i32(self.explosion.count);
+        for bullet in &self.bullets {
+            bullet.write_checksum(checksum);
+        }
+        checksum.write_u64(self.can_shot_enemy.len() as u64);
+        for i in &self.can_shot_enemy {
+            checksum.write_u64(*i as u64);
+        }
+        checksum.write_u64(self.shot_interval as u64);
+        checksum.write_u64(self.play_sound_index as u64);
+    }
 }
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use crate::dot_data::DotShape;
+use crate::load_image::ImageType;
+use crate::math::Vec2;
+use crate::renderer::{Renderer, TextAlign};
+use crate::sprite_pack::SpritePack;
+use crate::theme::{Color, Theme};
+
+// 等幅の文字1文字の幅と高さの比
+const CHAR_WIDTH_RATIO: f64 = 0.6;
+
+// メモリ上のrgba画素に描く(ブラウザの無いテストで画面を比べるのに使う)
+// 画像はビットマップの代わりにドットマップをそのまま拡大して描く
+pub struct FrameBuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>, // 1pixelをrgbaの4バイトで表す
+    theme: Theme,
+    images: HashMap<ImageType, (DotShape, [u8; 4])>,
+}
+
+impl FrameBuffer {
+    pub fn new(width: usize, height: usize, theme: &Theme, sprite_pack: &SpritePack) -> Self {
+        let images = ImageType::ret_all_types()
+            .into_iter()
+            .map(|image_type| {
+                let (name, color) = image_type.source();
+                (image_type, (sprite_pack.shape(name), theme.rgba(color)))
+            })
+            .collect();
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+            theme: theme.clone(),
+            images,
+        }
+    }
+    // 範囲[left, right)x[top, bottom)を画面内のpixelの範囲に丸める
+    fn clip(&self, left: f64, top: f64, right: f64, bottom: f64) -> (usize, usize, usize, usize) {
+        let clamp = |value: f64, max: usize| value.round().clamp(0., max as f64) as usize;
+        (
+            clamp(left, self.width),
+            clamp(top, self.height),
+            clamp(right, self.width),
+            clamp(bottom, self.height),
+        )
+    }
+    // 比べた結果を画像として見られるように、バイナリ形式のPPM画像(rgbのみ)に変換
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut bytes = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for rgba in self.pixels.chunks(4) {
+            bytes.extend_from_slice(&rgba[..3]);
+        }
+        bytes
+    }
+    // 画面を小さく保存するためのテキスト形式(rgbのみ)に変換
+    // 1行目は幅、高さ、色数。続いて1行に1色ずつ「色番号 rrggbb」、
+    // その後に画面の1行ごとに「色番号*連続するpixel数」を空白区切りで並べる(直前の行と同じなら「=」)
+    pub fn to_snapshot(&self) -> String {
+        let mut palette: Vec<[u8; 3]> = Vec::new();
+        let mut rows = Vec::new();
+        let mut previous: Option<&[u8]> = None;
+        for row in self.pixels.chunks(self.width * 4) {
+            if previous == Some(row) {
+                rows.push("=".to_string());
+                continue;
+            }
+            previous = Some(row);
+            let mut runs: Vec<(usize, usize)> = Vec::new();
+            for rgba in row.chunks(4) {
+                let rgb = [rgba[0], rgba[1], rgba[2]];
+                let index = match palette.iter().position(|color| *color == rgb) {
+                    Some(index) => index,
+                    None => {
+                        palette.push(rgb);
+                        palette.len() - 1
+                    }
+                };
+                match runs.last_mut() {
+                    Some((run_index, len)) if *run_index == index => *len += 1,
+                    _ => runs.push((index, 1)),
+                }
+            }
+            let runs: Vec<String> = runs
+                .iter()
+                .map(|(index, len)| format!("{}*{}", index, len))
+                .collect();
+            rows.push(runs.join(" "));
+        }
+        let mut text = format!("{} {} {}\n", self.width, self.height, palette.len());
+        for (index, [r, g, b]) in palette.iter().enumerate() {
+            text.push_str(&format!("{} {:02x}{:02x}{:02x}\n", index, r, g, b));
+        }
+        for row in rows {
+            text.push_str(&row);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+impl Renderer for FrameBuffer {
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
+        let rgba = self.theme.rgba(color);
+        let (left, top, right, bottom) = self.clip(x, y, x + width, y + height);
+        // 1行分の画素を作って各行に写す
+        let line = rgba.repeat(right.saturating_sub(left));
+        for py in top..bottom {
+            let index = (py * self.width + left) * 4;
+            self.pixels[index..index + line.len()].copy_from_slice(&line);
+        }
+    }
+    // 線に沿って1pixelごとに太さの分の正方形を塗る
+    fn draw_line(&mut self, from: Vec2, to: Vec2, line_width: f64, color: Color) {
+        let steps = (to.x - from.x)
+            .abs()
+            .max((to.y - from.y).abs())
+            .ceil()
+            .max(1.);
+        for i in 0..=steps as usize {
+            let t = i as f64 / steps;
+            let x = from.x + (to.x - from.x) * t;
+            let y = from.y + (to.y - from.y) * t;
+            self.fill_rect(
+                x - line_width / 2.,
+                y - line_width / 2.,
+                line_width,
+                line_width,
+                color,
+            );
+        }
+    }
+    // 5x7ドットの文字を、1文字の幅がsizeの0.6倍になるように拡大して描く
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, size: f64, align: TextAlign, color: Color) {
+        let char_width = size * CHAR_WIDTH_RATIO;
+        let dot = char_width / 6.;
+        let left = match align {
+            TextAlign::Start => x,
+            TextAlign::Center => x - char_width * text.chars().count() as f64 / 2.,
+        };
+        for (i, c) in text.chars().enumerate() {
+            let char_left = left + char_width * i as f64;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..5 {
+                    if bits & (0x10 >> column) != 0 {
+                        self.fill_rect(
+                            char_left + dot * column as f64,
+                            y - dot * (7 - row) as f64,
+                            dot,
+                            dot,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    fn draw_image(&mut self, image_type: &ImageType, x: f64, y: f64, width: f64, height: f64) {
+        let (left, top, right, bottom) = self.clip(x, y, x + width, y + height);
+        let (shape, rgba) = self.images.get(image_type).unwrap();
+        for py in top..bottom {
+            for px in left..right {
+                // 描く範囲の画素の中心に対応するドット
+                let dot_x = ((px as f64 + 0.5 - x) / width * shape.width as f64).floor();
+                let dot_y = ((py as f64 + 0.5 - y) / height * shape.height as f64).floor();
+                let dot = shape
+                    .dot_map
+                    .get(dot_y as usize)
+                    .and_then(|line| line.get(dot_x as usize));
+                if dot == Some(&true) {
+                    let index = (py * self.width + px) * 4;
+                    self.pixels[index..index + 4].copy_from_slice(rgba);
+                }
+            }
+        }
+    }
+}
+
+// 文字の5x7ドットの形(各行の下位5bit、上位のbitが左)
+// 小文字は大文字と同じ形で描き、用意していない文字は塗りつぶした四角で描く
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        ' ' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        '<' => [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+        '>' => [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '?' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+        _ => [0x1F; 7],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameState, InputFrame, Scene};
+    use crate::high_score::MemoryStorage;
+    use crate::render;
+    use crate::theme::ThemePreset;
+    use crate::title::Title;
+    use std::path::PathBuf;
+
+    const WIDTH: usize = 540;
+    const HEIGHT: usize = 600;
+    const SEED: u64 = 0x5EED;
+
+    // ゲームとフレームバッファを用意し、毎フレーム描画しながら進める
+    struct Screen {
+        game: GameState,
+        title: Title,
+        frame_buffer: FrameBuffer,
+    }
+
+    impl Screen {
+        fn new() -> Self {
+            let theme = ThemePreset::Classic.theme();
+            Screen {
+                game: GameState::new(
+                    WIDTH as f64,
+                    HEIGHT as f64,
+                    SEED,
+                    Box::new(MemoryStorage::default()),
+                ),
+                title: Title::new(WIDTH as f64, HEIGHT as f64),
+                frame_buffer: FrameBuffer::new(WIDTH, HEIGHT, &theme, &SpritePack::default()),
+            }
+        }
+        fn step(&mut self, input: InputFrame) {
+            self.game.step(&input);
+            render::render(&mut self.frame_buffer, &self.title, &self.game);
+        }
+        // 入力無しでframesフレーム進める
+        fn wait(&mut self, frames: usize) {
+            for _ in 0..frames {
+                self.step(InputFrame::default());
+            }
+        }
+        fn start_game(&mut self) {
+            self.wait(10);
+            self.step(InputFrame {
+                shot: true,
+                ..InputFrame::default()
+            });
+        }
+    }
+
+    // snapshots/name.txtと画素が一致するか確かめる
+    // UPDATE_SNAPSHOTS=1を付けて実行すると、期待する画像を書き換える
+    fn assert_snapshot(name: &str, frame_buffer: &FrameBuffer) {
+        let snapshot = frame_buffer.to_snapshot();
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("snapshots")
+            .join(format!("{}.txt", name));
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &snapshot).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+            panic!(
+                "{}を読み込めません(UPDATE_SNAPSHOTS=1で作成): {}",
+                path.display(),
+                err
+            )
+        });
+        if expected != snapshot {
+            // 比べやすいように実際の画像をテキスト形式とPPM画像で書き出す
+            let actual_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("target")
+                .join("snapshots")
+                .join(format!("{}.txt", name));
+            std::fs::create_dir_all(actual_path.parent().unwrap()).unwrap();
+            std::fs::write(&actual_path, &snapshot).unwrap();
+            std::fs::write(actual_path.with_extension("ppm"), frame_buffer.to_ppm()).unwrap();
+            let different = expected
+                .lines()
+                .zip(snapshot.lines())
+                .filter(|(expected, actual)| expected != actual)
+                .count();
+            panic!(
+                "{}の画像が異なります({}行): {}",
+                name,
+                different,
+                actual_path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn title_snapshot() {
+        let mut screen = Screen::new();
+        screen.wait(10);
+        assert_snapshot("title", &screen.frame_buffer);
+    }
+
+    #[test]
+    fn play_snapshot() {
+        let mut screen = Screen::new();
+        screen.start_game();
+        // インベーダーが動き始めてから、右に動きながら撃つ
+        screen.wait(180);
+        for i in 0..60 {
+            screen.step(InputFrame {
+                right: true,
+                shot: i % 20 == 0,
+                ..InputFrame::default()
+            });
+        }
+        assert!(matches!(screen.game.scene, Scene::Play));
+        assert_snapshot("play", &screen.frame_buffer);
+    }
+
+    #[test]
+    fn game_over_snapshot() {
+        let mut screen = Screen::new();
+        screen.start_game();
+        // 残機1で画面中央に立ち止まり、撃破されてゲームオーバー表示が出るまで進める
+        screen.game.player.life = 1;
+        screen.wait(120);
+        for _ in 0..90 {
+            screen.step(InputFrame {
+                right: true,
+                ..InputFrame::default()
+            });
+        }
+        for _ in 0..60 * 60 {
+            screen.wait(1);
+            let exploding = screen.game.player.exploding();
+            if matches!(screen.game.scene, Scene::GameOver(_)) && !exploding {
+                break;
+            }
+        }
+        assert!(matches!(screen.game.scene, Scene::GameOver(_)));
+        screen.wait(1);
+        assert_snapshot("game_over", &screen.frame_buffer);
+    }
+}
@@ -0,0 +1,801 @@
+use crate::checksum::Checksum;
+use crate::difficulty::{Difficulty, DifficultyConfig};
+use crate::enemy::EnemyManage;
+use crate::high_score::{HighScores, NameEntry, ScoreStorage};
+use crate::player::{self, Player};
+use crate::replay::Replay;
+use crate::rng::Rng;
+use crate::stage::{Stage, StageList};
+use crate::torchika::{self, Torchika};
+use crate::ufo::Ufo;
+
+pub enum Scene {
+    Title,            // タイトル画面
+    Pause,            // 一時停止状態
+    Play,             // ゲーム実行中
+    LaunchStage(i32), // ゲーム開始後、プレイヤーが操作可能になるまで
+    GameOver(i32),
+    NameEntry,         // ハイスコアを出した後のイニシャル入力
+    PlayerChange(i32), // 2人交互プレイで、次にプレイするプレイヤーを表示する
+    StagePreview,      // ステージエディタで編集中のステージの動きを確認する
+}
+
+// 1フレーム分の入力状態(対応するボタンが押されている時真)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputFrame {
+    pub left: bool,  // プレイヤーを左へ移動させる
+    pub right: bool, // プレイヤーを右へ移動させる
+    pub shot: bool,  // プレイヤーが弾を撃つ
+    pub pause: bool, // ポーズする
+    // 2人同時プレイでの2人目のプレイヤーの操作
+    pub left_2: bool,
+    pub right_2: bool,
+    pub shot_2: bool,
+    pub difficulty: bool, // 難易度を切り替える(タイトル画面のみ)
+}
+
+impl InputFrame {
+    // 各ボタンの状態を1ビットずつ詰める
+    pub fn to_bits(self) -> u8 {
+        self.left as u8
+            | (self.right as u8) << 1
+            | (self.shot as u8) << 2
+            | (self.pause as u8) << 3
+            | (self.left_2 as u8) << 4
+            | (self.right_2 as u8) << 5
+            | (self.shot_2 as u8) << 6
+            | (self.difficulty as u8) << 7
+    }
+    pub fn from_bits(bits: u8) -> Self {
+        InputFrame {
+            left: bits & 1 != 0,
+            right: bits & 1 << 1 != 0,
+            shot: bits & 1 << 2 != 0,
+            pause: bits & 1 << 3 != 0,
+            left_2: bits & 1 << 4 != 0,
+            right_2: bits & 1 << 5 != 0,
+            shot_2: bits & 1 << 6 != 0,
+            difficulty: bits & 1 << 7 != 0,
+        }
+    }
+    // どちらかで押されているボタンを押されているとする
+    pub fn merge(self, other: Self) -> Self {
+        InputFrame::from_bits(self.to_bits() | other.to_bits())
+    }
+    // 2人目のプレイヤーの操作を1人目と同じ形で取り出す
+    pub fn second_player(self) -> Self {
+        InputFrame {
+            left: self.left_2,
+            right: self.right_2,
+            shot: self.shot_2,
+            ..Default::default()
+        }
+    }
+}
+
+// 1フレーム分の入力を、前フレームからの変化と合わせて表す
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputSnapshot {
+    pub held: InputFrame,     // 押されているボタン
+    pub pressed: InputFrame,  // このフレームで押されたボタン
+    pub released: InputFrame, // このフレームで離されたボタン
+}
+
+impl InputSnapshot {
+    pub fn new(input: InputFrame, pre_input: InputFrame) -> Self {
+        let (bits, pre_bits) = (input.to_bits(), pre_input.to_bits());
+        InputSnapshot {
+            held: input,
+            pressed: InputFrame::from_bits(bits & !pre_bits),
+            released: InputFrame::from_bits(!bits & pre_bits),
+        }
+    }
+    // プレイヤーの操作
+    // セミオートでは射撃ボタンを押した瞬間のみ撃てる(撃つたびにボタンを離す必要がある)
+    pub fn player_input(&self, semi_auto: bool) -> InputFrame {
+        if !semi_auto {
+            return self.held;
+        }
+        InputFrame {
+            shot: self.pressed.shot,
+            shot_2: self.pressed.shot_2,
+            ..self.held
+        }
+    }
+}
+
+// タイトル画面で選ぶ遊び方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMode {
+    Single,    // 1人プレイ
+    Alternate, // 2人交互プレイ
+    CoOp,      // 2人同時プレイ
+}
+
+impl PlayMode {
+    pub const ALL: [PlayMode; 3] = [PlayMode::Single, PlayMode::Alternate, PlayMode::CoOp];
+    // リプレイファイルに保存する番号(1人プレイと2人交互プレイはプレイ人数と同じ)
+    pub fn to_u8(self) -> u8 {
+        match self {
+            PlayMode::Single => 1,
+            PlayMode::Alternate => 2,
+            PlayMode::CoOp => 3,
+        }
+    }
+    pub fn from_u8(value: u8) -> Option<Self> {
+        PlayMode::ALL.into_iter().find(|mode| mode.to_u8() == value)
+    }
+    pub fn player_count(self) -> usize {
+        match self {
+            PlayMode::Single => 1,
+            PlayMode::Alternate | PlayMode::CoOp => 2,
+        }
+    }
+}
+
+// シミュレーション中に発生した効果音の再生要求
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEvent {
+    PlayerShot,
+    PlayerExplosion,
+    InvaderExplosion,
+    InvaderMove(usize), // 再生する移動音の番号
+    UfoFlyingStart,
+    UfoFlyingStop,
+    UfoExplosion,
+}
+
+// 2人交互プレイで、交代して待っている側のプレイヤーの状態
+pub struct PlayerState {
+    pub player: Player,
+    pub enemy_manage: EnemyManage,
+    pub torchika_list: Vec<Torchika>,
+    pub stage_number: usize,
+}
+
+impl PlayerState {
+    // ステージ1から始める状態
+    fn new(
+        canvas_width: f64,
+        canvas_height: f64,
+        config: &DifficultyConfig,
+        first_stage: &Stage,
+    ) -> Self {
+        let mut player = Player::new(canvas_width, canvas_height, config);
+        player.all_reset();
+        let mut enemy_manage = EnemyManage::new(canvas_width, canvas_height, config);
+        enemy_manage.reset(first_stage);
+        PlayerState {
+            player,
+            enemy_manage,
+            torchika_list: torchika::ret_torchika_list(
+                canvas_width,
+                canvas_height,
+                &first_stage.torchika,
+            ),
+            stage_number: 1,
+        }
+    }
+}
+
+// 描画に依存しないゲーム全体の状態
+pub struct GameState {
+    pub player: Player,
+    pub enemy_manage: EnemyManage,
+    pub ufo: Ufo,
+    pub torchika_list: Vec<Torchika>,
+    pub scene: Scene,
+    pub stage_number: usize,   // 最初は1、最終はstagesのステージ数
+    pub stages: StageList,     // 各ステージの定義(変更は次のゲームから有効)
+    stages_in_game: StageList, // 現在のゲームのステージの定義
+    pub play_mode: PlayMode,
+    pub difficulty: Difficulty,              // タイトル画面で選ぶ
+    pub current_player: usize,               // プレイ中のプレイヤーの番号(1人目が0)
+    pub waiting_player: Option<PlayerState>, // 2人交互プレイで、待っている側のプレイヤー
+    pub second_player: Option<Player>,       // 2人同時プレイでの2人目のプレイヤー
+    pub canvas_width: f64,
+    pub canvas_height: f64,
+    pub sounds: Vec<SoundEvent>,     // 再生待ちの効果音(描画側で取り出す)
+    seed: u64,                       // 現在(タイトル画面では次)のゲームの乱数のシード値
+    rng: Rng,                        // ゲーム内のすべての乱数の生成元
+    recording: Option<Replay>,       // 現在のゲームの記録(ゲーム中のみSome)
+    pub last_replay: Option<Replay>, // 最後に終了したゲームの記録
+    pub last_checksum: u64,          // 直前のフレームを進めた後の状態のハッシュ値
+    pub high_scores: HighScores,
+    pub name_entry: NameEntry,
+    pub semi_auto: bool,     // 真ならばセミオート射撃(変更は次のゲームから有効)
+    semi_auto_in_game: bool, // 現在のゲームがセミオート射撃ならば真
+    pending_scores: Vec<(usize, usize)>, // イニシャル入力を待つ(プレイヤーの番号, 得点)
+    pre_input: InputFrame,   // 前フレームの入力
+    need_to_stage_init: bool, // 真ならばステージの初期化が必要
+    new_game: bool,          // 真ならば残機、点数などをすべてリセットする
+}
+
+impl GameState {
+    pub fn new(
+        canvas_width: f64,
+        canvas_height: f64,
+        seed: u64,
+        score_storage: Box<dyn ScoreStorage>,
+    ) -> Self {
+        let config = Difficulty::Normal.config();
+        let stages = StageList::default();
+        GameState {
+            player: Player::new(canvas_width, canvas_height, &config),
+            enemy_manage: EnemyManage::new(canvas_width, canvas_height, &config),
+            ufo: Ufo::new(&config),
+            torchika_list: torchika::ret_torchika_list(
+                canvas_width,
+                canvas_height,
+                &stages.stage(1).torchika,
+            ),
+            scene: Scene::Title,
+            stage_number: 1,
+            stages_in_game: stages.clone(),
+            stages,
+            play_mode: PlayMode::Single,
+            difficulty: Difficulty::Normal,
+            current_player: 0,
+            waiting_player: None,
+            second_player: None,
+            canvas_width,
+            canvas_height,
+            sounds: Vec::new(),
+            seed,
+            rng: Rng::new(seed),
+            recording: None,
+            last_replay: None,
+            last_checksum: 0,
+            high_scores: HighScores::new(score_storage),
+            name_entry: NameEntry::new(0, 0),
+            semi_auto: false,
+            semi_auto_in_game: false,
+            pending_scores: Vec::new(),
+            pre_input: InputFrame::default(),
+            need_to_stage_init: true,
+            new_game: true,
+        }
+    }
+
+    // 1フレーム分ゲームを進める
+    pub fn step(&mut self, input: &InputFrame) {
+        let input_snapshot = InputSnapshot::new(*input, self.pre_input);
+        self.pre_input = *input;
+        self.update(&input_snapshot);
+        self.last_checksum = self.checksum();
+        // ゲーム中の入力を記録
+        if let Some(replay) = &mut self.recording {
+            replay.record(input, self.last_checksum);
+        }
+        // ゲームオーバー表示が終わったら記録を終える
+        if let Scene::Title | Scene::NameEntry = self.scene {
+            if let Some(mut replay) = self.recording.take() {
+                replay.finish(self.player.bullet.score.sum, self.last_checksum);
+                self.last_replay = Some(replay);
+            }
+        }
+    }
+
+    // ゲームの進行に関わるすべての状態のハッシュ値(同じ展開をたどったか調べるのに使う)
+    // 描画用の状態(前回の描画位置など)は含めない
+    pub fn checksum(&self) -> u64 {
+        let mut checksum = Checksum::new();
+        let (scene, cnt) = match self.scene {
+            Scene::Title => (0, 0),
+            Scene::Pause => (1, 0),
+            Scene::Play => (2, 0),
+            Scene::LaunchStage(cnt) => (3, cnt),
+            Scene::GameOver(cnt) => (4, cnt),
+            Scene::NameEntry => (5, 0),
+            Scene::PlayerChange(cnt) => (6, cnt),
+            Scene::StagePreview => (7, 0),
+        };
+        checksum.write_i32(scene);
+        checksum.write_i32(cnt);
+        checksum.write_u64(self.stage_number as u64);
+        checksum.write_u64(self.rng.state());
+        checksum.write_bool(self.need_to_stage_init);
+        checksum.write_bool(self.new_game);
+        checksum.write_bytes(&[self.pre_input.to_bits()]);
+        checksum.write_bool(self.semi_auto_in_game);
+
+        self.player.write_checksum(&mut checksum);
+        self.enemy_manage.write_checksum(&mut checksum);
+        self.ufo.write_checksum(&mut checksum);
+        for torchika in &self.torchika_list {
+            torchika.write_checksum(&mut checksum);
+        }
+
+        checksum.write_bytes(&[self.play_mode.to_u8(), self.difficulty.to_u8()]);
+        checksum.write_u64(self.current_player as u64);
+        if let Some(second_player) = &self.second_player {
+            second_player.write_checksum(&mut checksum);
+        }
+        if let Some(waiting) = &self.waiting_player {
+            checksum.write_u64(waiting.stage_number as u64);
+            waiting.player.write_checksum(&mut checksum);
+            waiting.enemy_manage.write_checksum(&mut checksum);
+            for torchika in &waiting.torchika_list {
+                torchika.write_checksum(&mut checksum);
+            }
+        }
+        checksum.finish()
+    }
+
+    // 1人目、2人目の得点(1人プレイでは2人目はNone)
+    pub fn player_scores(&self) -> (usize, Option<usize>) {
+        let current = self.player.bullet.score.sum;
+        if let Some(second_player) = &self.second_player {
+            return (current, Some(second_player.bullet.score.sum));
+        }
+        match &self.waiting_player {
+            None => (current, None),
+            Some(waiting) => {
+                let waiting = waiting.player.bullet.score.sum;
+                if self.current_player == 0 {
+                    (current, Some(waiting))
+                } else {
+                    (waiting, Some(current))
+                }
+            }
+        }
+    }
+    // 待っている側のプレイヤーに残機があれば真
+    fn waiting_player_alive(&self) -> bool {
+        self.waiting_player
+            .as_ref()
+            .is_some_and(|waiting| waiting.player.life > 0)
+    }
+    // 2人同時プレイで、残機が無くなった2人目のプレイヤーも含めて全員の残機が無ければ真
+    fn all_players_down(&self) -> bool {
+        self.player.life <= 0
+            && self
+                .second_player
+                .as_ref()
+                .is_none_or(|second_player| second_player.life <= 0)
+    }
+    // プレイ中のプレイヤーの状態を保存し、待っていたプレイヤーの状態に入れ替える
+    fn switch_player(&mut self) {
+        let Some(waiting) = self.waiting_player.as_mut() else {
+            return;
+        };
+        // 交代する側の残った敵弾は消しておく
+        self.enemy_manage.clear_bullets();
+        std::mem::swap(&mut self.player, &mut waiting.player);
+        std::mem::swap(&mut self.enemy_manage, &mut waiting.enemy_manage);
+        std::mem::swap(&mut self.torchika_list, &mut waiting.torchika_list);
+        std::mem::swap(&mut self.stage_number, &mut waiting.stage_number);
+        self.current_player = 1 - self.current_player;
+
+        // 撃破されて交代したプレイヤーは再出撃から始める
+        if self.player.break_cnt.is_some() {
+            self.player.revive();
+        }
+        self.ufo.reset(&mut self.sounds);
+    }
+    // イニシャル入力を待つ得点のうち、ハイスコア表に載るものがあれば入力を始めて真を返す
+    fn start_name_entry(&mut self) -> bool {
+        while !self.pending_scores.is_empty() {
+            let (player_number, score) = self.pending_scores.remove(0);
+            if self.high_scores.qualifies(score) {
+                self.name_entry = NameEntry::new(player_number, score);
+                return true;
+            }
+        }
+        false
+    }
+
+    // 新しくゲームを始める
+    fn start_new_game(&mut self) {
+        // 同じシード値と入力からは同じゲームが再現されるように、
+        // 前のゲームの状態をすべて捨てる
+        // 選んだ難易度の設定でインベーダー、UFO、プレイヤーを作り直す
+        let config = self.difficulty.config();
+        self.stages_in_game = self.stages.clone();
+        self.stage_number = 1;
+        self.rng = Rng::new(self.seed);
+        self.enemy_manage = EnemyManage::new(self.canvas_width, self.canvas_height, &config);
+        self.ufo = Ufo::new(&config);
+        self.torchika_list = torchika::ret_torchika_list(
+            self.canvas_width,
+            self.canvas_height,
+            &self.stages_in_game.stage(1).torchika,
+        );
+        self.semi_auto_in_game = self.semi_auto;
+        self.player = Player::new(self.canvas_width, self.canvas_height, &config);
+        self.player.all_reset();
+        self.current_player = 0;
+        self.waiting_player = (self.play_mode == PlayMode::Alternate).then(|| {
+            PlayerState::new(
+                self.canvas_width,
+                self.canvas_height,
+                &config,
+                self.stages_in_game.stage(1),
+            )
+        });
+        self.second_player = (self.play_mode == PlayMode::CoOp).then(|| {
+            let mut second_player =
+                Player::new_second(self.canvas_width, self.canvas_height, &config);
+            second_player.all_reset();
+            second_player
+        });
+        self.need_to_stage_init = true;
+        self.new_game = true;
+    }
+
+    fn update(&mut self, input: &InputSnapshot) {
+        let pressed = &input.pressed;
+        let player_input = input.player_input(self.semi_auto_in_game);
+        match self.scene {
+            Scene::Title => {
+                // スタートボタンが押されたらゲーム開始
+                // (リプレイはこのフレームから記録するため、同じフレームの他の入力は使わない)
+                if pressed.shot {
+                    self.start_new_game();
+                    // 2人交互プレイでは、最初に1人目のプレイヤーを表示する
+                    self.scene = if self.play_mode == PlayMode::Alternate {
+                        Scene::PlayerChange(120)
+                    } else {
+                        Scene::LaunchStage(120)
+                    };
+                    // 開始したフレームから記録する
+                    self.recording = Some(Replay::new(
+                        self.seed,
+                        self.canvas_width,
+                        self.canvas_height,
+                        self.play_mode,
+                        self.difficulty,
+                        self.semi_auto_in_game,
+                        self.stages_in_game.checksum(),
+                    ));
+                    return;
+                }
+                // 左右で遊び方を選ぶ(押した瞬間のみ1つずつ動かす)
+                let index = PlayMode::ALL
+                    .iter()
+                    .position(|mode| *mode == self.play_mode)
+                    .unwrap_or(0);
+                if pressed.left && index > 0 {
+                    self.play_mode = PlayMode::ALL[index - 1];
+                }
+                if pressed.right && index + 1 < PlayMode::ALL.len() {
+                    self.play_mode = PlayMode::ALL[index + 1];
+                }
+                // 難易度を切り替える
+                if pressed.difficulty {
+                    self.difficulty = self.difficulty.next();
+                }
+            }
+            Scene::Pause => {
+                // ポーズボタンが押されたらゲーム再開
+                if pressed.pause {
+                    self.scene = Scene::Play;
+                }
+            }
+            Scene::LaunchStage(cnt) => {
+                // インベーダーを全滅させた後は休憩のため長めに間をおく
+                if self.stage_number > 1 && cnt > 120 {
+                    self.scene = Scene::LaunchStage(cnt - 1);
+                    return;
+                }
+                if self.need_to_stage_init {
+                    // ステージが進む(新しいゲームの初期化はゲーム開始時に済んでいる)
+                    if !self.new_game {
+                        self.player.stage_reset();
+                        if let Some(second_player) = &mut self.second_player {
+                            second_player.stage_reset();
+                        }
+                    }
+                    let stage = self.stages_in_game.stage(self.stage_number);
+                    self.enemy_manage.reset(stage);
+                    self.torchika_list = torchika::ret_torchika_list(
+                        self.canvas_width,
+                        self.canvas_height,
+                        &stage.torchika,
+                    );
+                    self.ufo.reset(&mut self.sounds);
+
+                    // 初期化は最初のみ
+                    self.need_to_stage_init = false;
+                    self.new_game = false;
+                }
+
+                // 敵インベーダーの処理
+                // プレイヤーが操作可能になるまで敵は動くが攻撃しない
+                self.enemy_manage.set_shot_interval(0);
+                self.enemy_manage.update(
+                    &mut players(&mut self.player, &mut self.second_player),
+                    &mut self.torchika_list,
+                    &mut self.rng,
+                    &mut self.sounds,
+                );
+
+                // 一定時間経過するまで繰り返す
+                if cnt < 0 {
+                    self.scene = Scene::Play;
+                    // UFOの出現タイマーを初期化する
+                    self.ufo.reset_timer();
+
+                    log::info!("Stage{} start.", self.stage_number);
+                } else {
+                    self.scene = Scene::LaunchStage(cnt - 1);
+                }
+            }
+            Scene::Play => {
+                // ポーズボタンが押されたらゲーム一時停止
+                if pressed.pause {
+                    self.scene = Scene::Pause;
+                }
+                // プレイヤーの処理
+                self.player
+                    .update(&player_input, &mut self.torchika_list, &mut self.sounds);
+                if let Some(second_player) = &mut self.second_player {
+                    second_player.update(
+                        &player_input.second_player(),
+                        &mut self.torchika_list,
+                        &mut self.sounds,
+                    );
+                }
+                let mut players = players(&mut self.player, &mut self.second_player);
+                // 敵インベーダーの処理
+                self.enemy_manage.update(
+                    &mut players,
+                    &mut self.torchika_list,
+                    &mut self.rng,
+                    &mut self.sounds,
+                );
+                // UFOの処理
+                let mut player_bullets: Vec<&mut player::Bullet> = players
+                    .iter_mut()
+                    .map(|player| &mut player.bullet)
+                    .collect();
+                self.ufo
+                    .update(self.canvas_width, &mut player_bullets, &mut self.sounds);
+
+                if let Some(enemy_pos_y) = self.enemy_manage.nadir_y() {
+                    for player in players.iter_mut() {
+                        // 敵インベーダーがプレイヤーの高さまで侵攻した場合
+                        if player.pos.y - player.height / 2. < enemy_pos_y
+                            && player.break_cnt.is_none()
+                        {
+                            // プレイヤーは破壊され、残機もすべて失う
+                            player.break_cnt = Some(player.revival_set_cnt);
+                            player.life = 0;
+                        }
+                    }
+                } else {
+                    // インベーダーが全滅した場合
+                    // ステージを初期化する
+                    self.need_to_stage_init = true;
+                    self.new_game = false;
+                    // 最終ステージの次は定義で指定したステージに戻る
+                    self.stage_number = self.stages_in_game.next(self.stage_number);
+                    self.scene = Scene::LaunchStage(240);
+                }
+                // プレイヤーの残機が無くなったら
+                if self.all_players_down() && !self.waiting_player_alive() {
+                    // ゲームオーバー
+                    self.scene = Scene::GameOver(140);
+                } else if self.player.break_cnt.is_some_and(|cnt| cnt < 0)
+                    && self.waiting_player_alive()
+                {
+                    // 2人交互プレイでは、撃破されたプレイヤーの爆発エフェクトが終わったら交代する
+                    self.switch_player();
+                    self.scene = Scene::PlayerChange(120);
+                }
+            }
+            Scene::PlayerChange(cnt) => {
+                // 一定時間表示したらステージを開始する
+                self.scene = if cnt < 0 {
+                    Scene::LaunchStage(120)
+                } else {
+                    Scene::PlayerChange(cnt - 1)
+                };
+            }
+            Scene::GameOver(cnt) => {
+                self.new_game = true;
+                // プレイヤーの爆発エフェクトを最後まで表示
+                let mut players = players(&mut self.player, &mut self.second_player);
+                if players.iter().any(|player| player.exploding()) {
+                    for player in players.iter_mut() {
+                        player.update(
+                            &InputFrame::default(),
+                            &mut self.torchika_list,
+                            &mut self.sounds,
+                        );
+                    }
+                } else {
+                    // プレイヤーの爆発エフェクト表示が終わったら一定時間ゲームオーバー表示
+                    self.scene = Scene::GameOver(cnt - 1);
+                }
+                if cnt < 0 {
+                    self.ufo.reset(&mut self.sounds);
+                    // 次のゲームのシード値は今回のゲームの乱数から決める
+                    self.seed = self.rng.next_u64();
+                    // ハイスコア表に載る得点があればイニシャルを入力する
+                    let (score_1, score_2) = self.player_scores();
+                    self.pending_scores = vec![(1, score_1)];
+                    if let Some(score_2) = score_2 {
+                        self.pending_scores.push((2, score_2));
+                    }
+                    // 得点の高いプレイヤーから入力する
+                    self.pending_scores
+                        .sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+                    if self.start_name_entry() {
+                        self.scene = Scene::NameEntry;
+                    } else {
+                        // タイトルに戻る
+                        self.scene = Scene::Title;
+                    }
+                }
+            }
+            Scene::NameEntry => {
+                if let Some(name) =
+                    self.name_entry
+                        .update(pressed.left, pressed.right, pressed.shot)
+                {
+                    self.high_scores.insert(name, self.name_entry.score);
+                    // 全員の入力が終わったらタイトルに戻る
+                    if !self.start_name_entry() {
+                        self.scene = Scene::Title;
+                    }
+                }
+            }
+            Scene::StagePreview => {
+                // 敵は攻撃せずに進み続ける
+                self.enemy_manage.set_shot_interval(0);
+                self.enemy_manage.update(
+                    &mut [],
+                    &mut self.torchika_list,
+                    &mut self.rng,
+                    &mut self.sounds,
+                );
+                // 射撃かポーズボタンが押されるか、プレイヤーの高さまで侵攻したらタイトルに戻る
+                let invaded = self.enemy_manage.nadir_y().is_none_or(|enemy_pos_y| {
+                    self.player.pos.y - self.player.height / 2. < enemy_pos_y
+                });
+                if pressed.shot || pressed.pause || invaded {
+                    self.scene = Scene::Title;
+                }
+            }
+        }
+    }
+    // タイトル画面からstageのインベーダーの動きを表示する(タイトル画面以外では偽を返す)
+    pub fn start_preview(&mut self, stage: &Stage) -> bool {
+        if !matches!(self.scene, Scene::Title) {
+            return false;
+        }
+        let config = self.difficulty.config();
+        self.enemy_manage = EnemyManage::new(self.canvas_width, self.canvas_height, &config);
+        self.enemy_manage.reset(stage);
+        self.torchika_list =
+            torchika::ret_torchika_list(self.canvas_width, self.canvas_height, &stage.torchika);
+        self.scene = Scene::StagePreview;
+        true
+    }
+}
+
+// 2人同時プレイでは2人、それ以外では1人のプレイヤーをまとめる
+fn players<'a>(
+    player: &'a mut Player,
+    second_player: &'a mut Option<Player>,
+) -> Vec<&'a mut Player> {
+    std::iter::once(player)
+        .chain(second_player.as_mut())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::high_score::MemoryStorage;
+
+    const WIDTH: f64 = 540.;
+    const HEIGHT: f64 = 600.;
+
+    fn new_game(seed: u64) -> GameState {
+        GameState::new(WIDTH, HEIGHT, seed, Box::new(MemoryStorage::default()))
+    }
+
+    // タイトル画面で開始し、左右に動きながら撃ち続ける入力
+    fn script(frame: usize) -> InputFrame {
+        InputFrame {
+            left: frame % 240 < 120,
+            right: frame % 240 >= 120,
+            shot: frame == 10 || frame.is_multiple_of(30),
+            ..InputFrame::default()
+        }
+    }
+
+    // 比べるための状態(自機、弾、得点、インベーダー、敵の弾、UFOの位置と生死)
+    fn state(game: &GameState) -> Vec<(f64, f64, bool)> {
+        let player = &game.player;
+        std::iter::once((player.pos.x, player.pos.y, player.life > 0))
+            .chain(std::iter::once((
+                player.bullet.score.sum as f64,
+                0.,
+                player.bullet.live,
+            )))
+            .chain(
+                game.enemy_manage
+                    .enemys_list
+                    .iter()
+                    .map(|enemy| (enemy.pos.x, enemy.pos.y, enemy.live)),
+            )
+            .chain(
+                game.enemy_manage
+                    .bullets
+                    .iter()
+                    .map(|bullet| (bullet.pos.x, bullet.pos.y, bullet.live)),
+            )
+            .chain(std::iter::once((
+                game.ufo.pos.x,
+                game.ufo.pos.y,
+                game.ufo.explosion.live,
+            )))
+            .collect()
+    }
+
+    // framesフレーム進め、毎フレームの状態を返す
+    fn run(game: &mut GameState, frames: usize) -> Vec<Vec<(f64, f64, bool)>> {
+        (0..frames)
+            .map(|frame| {
+                game.step(&script(frame));
+                state(game)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shot_starts_game() {
+        let mut game = new_game(0x5EED);
+        game.step(&InputFrame::default());
+        assert!(matches!(game.scene, Scene::Title));
+        game.step(&InputFrame {
+            shot: true,
+            ..InputFrame::default()
+        });
+        assert!(matches!(game.scene, Scene::LaunchStage(_)));
+    }
+
+    #[test]
+    fn step_is_deterministic() {
+        let mut game_a = new_game(0x5EED);
+        let mut game_b = new_game(0x5EED);
+        assert_eq!(run(&mut game_a, 1800), run(&mut game_b, 1800));
+        assert!(matches!(game_a.scene, Scene::Play));
+    }
+
+    #[test]
+    fn different_seed_diverges() {
+        let mut game_a = new_game(1);
+        let mut game_b = new_game(2);
+        assert_ne!(run(&mut game_a, 1800), run(&mut game_b, 1800));
+    }
+
+    #[test]
+    fn checksum_follows_state() {
+        let mut games: Vec<GameState> = [1, 1, 2].into_iter().map(new_game).collect();
+        for game in &mut games {
+            run(game, 1800);
+        }
+        assert_eq!(games[0].last_checksum, games[1].last_checksum);
+        assert_ne!(games[0].last_checksum, games[2].last_checksum);
+    }
+
+    #[test]
+    fn checksum_does_not_change_state() {
+        let mut game = new_game(0x5EED);
+        run(&mut game, 600);
+        assert_eq!(game.checksum(), game.last_checksum);
+        assert_eq!(game.checksum(), game.checksum());
+    }
+
+    // ゲームの動きを変えるとこの値が変わる(その場合はreplay::SIMULATION_VERSIONも上げる)
+    #[test]
+    fn checksum_is_stable() {
+        let mut game = new_game(0x5EED);
+        run(&mut game, 1800);
+        assert_eq!(game.checksum(), 0x5b9b_2ab4_8179_a02e);
+    }
+}
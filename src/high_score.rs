@@ -0,0 +1,219 @@
+use web_sys::window;
+
+// 記録する順位の数
+const MAX_ENTRIES: usize = 10;
+// localStorageに保存するときのキー
+const LOCAL_STORAGE_KEY: &str = "invader-yew-high-scores";
+
+// ハイスコアの保存先
+// ブラウザではlocalStorage、それ以外ではメモリ上に保存する
+pub trait ScoreStorage {
+    fn load(&self) -> Option<String>;
+    fn save(&mut self, data: &str);
+}
+
+// メモリ上に保存する(ページを閉じると消える)
+#[derive(Default)]
+pub struct MemoryStorage {
+    data: Option<String>,
+}
+
+impl MemoryStorage {
+    pub fn new(data: String) -> Self {
+        MemoryStorage { data: Some(data) }
+    }
+}
+
+impl ScoreStorage for MemoryStorage {
+    fn load(&self) -> Option<String> {
+        self.data.clone()
+    }
+    fn save(&mut self, data: &str) {
+        self.data = Some(data.to_string());
+    }
+}
+
+// ブラウザのlocalStorageに保存する
+pub struct LocalStorage;
+
+impl ScoreStorage for LocalStorage {
+    fn load(&self) -> Option<String> {
+        let storage = window()?.local_storage().ok()??;
+        storage.get_item(LOCAL_STORAGE_KEY).ok()?
+    }
+    fn save(&mut self, data: &str) {
+        let storage = window().and_then(|window| window.local_storage().ok().flatten());
+        if let Some(storage) = storage {
+            if storage.set_item(LOCAL_STORAGE_KEY, data).is_err() {
+                log::warn!("ハイスコアを保存できませんでした。");
+            }
+        }
+    }
+}
+
+pub struct ScoreEntry {
+    pub name: String, // 3文字のイニシャル
+    pub score: usize,
+}
+
+// 得点の高い順に並べたハイスコア表
+pub struct HighScores {
+    pub entries: Vec<ScoreEntry>,
+    storage: Box<dyn ScoreStorage>,
+}
+
+impl HighScores {
+    pub fn new(storage: Box<dyn ScoreStorage>) -> Self {
+        // 1行に1件、"イニシャル 得点"の形式で保存されている
+        let mut entries: Vec<ScoreEntry> = storage
+            .load()
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (name, score) = line.split_once(' ')?;
+                Some(ScoreEntry {
+                    name: name.to_string(),
+                    score: score.parse().ok()?,
+                })
+            })
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        entries.truncate(MAX_ENTRIES);
+        HighScores { entries, storage }
+    }
+    // 保存されている形式の文字列を返す
+    pub fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| format!("{} {}\n", entry.name, entry.score))
+            .collect()
+    }
+    // 表の最高得点(表が空なら0)
+    pub fn best(&self) -> usize {
+        self.entries.first().map_or(0, |entry| entry.score)
+    }
+    // 得点scoreが表に載る場合に真を返す
+    pub fn qualifies(&self, score: usize) -> bool {
+        score > 0
+            && (self.entries.len() < MAX_ENTRIES
+                || self.entries.iter().any(|entry| entry.score < score))
+    }
+    // 表に追加して保存する(同点の場合は先に記録した方が上位)
+    pub fn insert(&mut self, name: String, score: usize) {
+        let rank = self
+            .entries
+            .iter()
+            .position(|entry| entry.score < score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(rank, ScoreEntry { name, score });
+        self.entries.truncate(MAX_ENTRIES);
+        let text = self.to_text();
+        self.storage.save(&text);
+    }
+}
+
+// ゲームオーバー後のイニシャル入力
+pub struct NameEntry {
+    pub player_number: usize, // 入力するプレイヤー(1人目は1)
+    pub score: usize,         // 表に載せる得点
+    pub letters: [u8; 3],     // 入力中のイニシャル(ASCIIの大文字)
+    pub cursor: usize,        // 入力中の文字の位置
+}
+
+impl NameEntry {
+    pub fn new(player_number: usize, score: usize) -> Self {
+        NameEntry {
+            player_number,
+            score,
+            letters: [b'A'; 3],
+            cursor: 0,
+        }
+    }
+    // 左右で文字を選び、ショットで確定する(引数は各ボタンを押した瞬間のみ真)
+    // 3文字すべて確定したらSome(イニシャル)を返す
+    pub fn update(&mut self, left: bool, right: bool, shot: bool) -> Option<String> {
+        let letter = &mut self.letters[self.cursor];
+        if left {
+            *letter = if *letter == b'A' { b'Z' } else { *letter - 1 };
+        }
+        if right {
+            *letter = if *letter == b'Z' { b'A' } else { *letter + 1 };
+        }
+        if shot {
+            self.cursor += 1;
+            if self.cursor == self.letters.len() {
+                return Some(String::from_utf8_lossy(&self.letters).into_owned());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // 保存された文字列をテストから読めるようにする
+    #[derive(Clone, Default)]
+    struct SharedStorage(Rc<RefCell<MemoryStorage>>);
+
+    impl ScoreStorage for SharedStorage {
+        fn load(&self) -> Option<String> {
+            self.0.borrow().load()
+        }
+        fn save(&mut self, data: &str) {
+            self.0.borrow_mut().save(data);
+        }
+    }
+
+    fn names(high_scores: &HighScores) -> Vec<&str> {
+        high_scores
+            .entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn insert_keeps_descending_order() {
+        let mut high_scores = HighScores::new(Box::new(MemoryStorage::default()));
+        assert_eq!(high_scores.best(), 0);
+        high_scores.insert("BBB".to_string(), 200);
+        high_scores.insert("AAA".to_string(), 300);
+        high_scores.insert("CCC".to_string(), 100);
+        // 同点なら先に記録した方が上位
+        high_scores.insert("DDD".to_string(), 200);
+        assert_eq!(names(&high_scores), ["AAA", "BBB", "DDD", "CCC"]);
+        assert_eq!(high_scores.best(), 300);
+    }
+
+    #[test]
+    fn keeps_only_top_entries() {
+        let mut high_scores = HighScores::new(Box::new(MemoryStorage::default()));
+        for score in 1..=MAX_ENTRIES {
+            high_scores.insert("AAA".to_string(), score * 10);
+        }
+        assert!(!high_scores.qualifies(10));
+        assert!(high_scores.qualifies(11));
+        high_scores.insert("BBB".to_string(), 11);
+        assert_eq!(high_scores.entries.len(), MAX_ENTRIES);
+        assert_eq!(high_scores.entries.last().unwrap().score, 11);
+        assert!(!high_scores.qualifies(0));
+    }
+
+    #[test]
+    fn loads_saved_entries_sorted() {
+        let storage = SharedStorage::default();
+        let mut high_scores = HighScores::new(Box::new(storage.clone()));
+        high_scores.insert("AAA".to_string(), 100);
+        high_scores.insert("BBB".to_string(), 500);
+        assert_eq!(storage.load().unwrap(), "BBB 500\nAAA 100\n");
+
+        // 並びが崩れた行や壊れた行があっても読み込める
+        let storage = MemoryStorage::new("AAA 100\nbroken\nCCC 900\nBBB x\n".to_string());
+        let high_scores = HighScores::new(Box::new(storage));
+        assert_eq!(names(&high_scores), ["CCC", "AAA"]);
+    }
+}
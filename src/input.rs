@@ -1,20 +1,286 @@
+use crate::game::InputFrame;
+use crate::viewport;
 use std::cell::RefCell;
 use std::rc::Rc;
 use wasm_bindgen::{prelude::Closure, JsCast};
-use web_sys::HtmlCanvasElement;
+use web_sys::{HtmlCanvasElement, HtmlInputElement};
+use yew::Callback;
 
-// 対応するキーが押されている時真
-#[derive(Debug)]
+// キーやボタンに割り当てるゲームの操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Left,  // プレイヤーを左へ移動させる
+    Right, // プレイヤーを右へ移動させる
+    Shot,  // プレイヤーが弾を撃つ
+    Pause, // ポーズする
+    // 2人同時プレイでの2人目のプレイヤーの操作
+    Left2,
+    Right2,
+    Shot2,
+    Difficulty, // タイトル画面で難易度を切り替える
+    Fullscreen, // 全画面表示を切り替える(ゲームの入力には含めない)
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Left,
+        Action::Right,
+        Action::Shot,
+        Action::Pause,
+        Action::Left2,
+        Action::Right2,
+        Action::Shot2,
+        Action::Difficulty,
+        Action::Fullscreen,
+    ];
+    // 保存する時の名前
+    fn name(self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Shot => "shot",
+            Action::Pause => "pause",
+            Action::Left2 => "left_2",
+            Action::Right2 => "right_2",
+            Action::Shot2 => "shot_2",
+            Action::Difficulty => "difficulty",
+            Action::Fullscreen => "fullscreen",
+        }
+    }
+    // 設定画面に表示する名前
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Shot => "Shot",
+            Action::Pause => "Pause",
+            Action::Left2 => "2P Left",
+            Action::Right2 => "2P Right",
+            Action::Shot2 => "2P Shot",
+            Action::Difficulty => "Difficulty",
+            Action::Fullscreen => "Fullscreen",
+        }
+    }
+    // 初期設定で割り当てるキー(KeyboardEvent.code)
+    fn default_codes(self) -> &'static [&'static str] {
+        match self {
+            Action::Left => &["ArrowLeft", "KeyA"],
+            Action::Right => &["ArrowRight", "KeyD"],
+            Action::Shot => &["Space", "Enter", "KeyZ"],
+            Action::Pause => &["Escape"],
+            Action::Left2 => &["KeyJ"],
+            Action::Right2 => &["KeyL"],
+            Action::Shot2 => &["KeyK"],
+            Action::Difficulty => &["KeyX"],
+            Action::Fullscreen => &["KeyF"],
+        }
+    }
+}
+
+// localStorageに保存するときのキー
+const LOCAL_STORAGE_KEY: &str = "invader-yew-key-bindings";
+
+// キー(キーボード上の位置を表すKeyboardEvent.code)からゲームの操作への割り当て
+// 1つのキーは1つの操作にのみ割り当てられる
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputMap {
+    bindings: Vec<(String, Action)>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        InputMap {
+            bindings: Action::ALL
+                .iter()
+                .flat_map(|action| {
+                    action
+                        .default_codes()
+                        .iter()
+                        .map(|code| (code.to_string(), *action))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl InputMap {
+    // 保存された割り当てを読み込む(無ければ初期設定)
+    pub fn load() -> Self {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        match storage.and_then(|storage| storage.get_item(LOCAL_STORAGE_KEY).ok().flatten()) {
+            Some(text) => InputMap::from_text(&text),
+            None => InputMap::default(),
+        }
+    }
+    fn save(&self) {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        if let Some(storage) = storage {
+            if storage
+                .set_item(LOCAL_STORAGE_KEY, &self.to_text())
+                .is_err()
+            {
+                log::warn!("キーの割り当てを保存できませんでした。");
+            }
+        }
+    }
+    // 1行に1つの操作、"操作の名前 キー キー ..."の形式
+    fn to_text(&self) -> String {
+        Action::ALL
+            .iter()
+            .map(|action| format!("{} {}\n", action.name(), self.codes(*action).join(" ")))
+            .collect()
+    }
+    // 不正な行や、他の操作と重なるキーは無視する
+    // 割り当てるキーが無くなった操作は初期設定に戻す
+    fn from_text(text: &str) -> Self {
+        let mut input_map = InputMap {
+            bindings: Vec::new(),
+        };
+        for line in text.lines() {
+            let mut words = line.split_whitespace();
+            let Some(name) = words.next() else {
+                continue;
+            };
+            let Some(action) = Action::ALL.into_iter().find(|action| action.name() == name) else {
+                log::warn!("不明な操作のキー割り当てを無視しました: {}", name);
+                continue;
+            };
+            for code in words {
+                if let Some(other) = input_map.action(code) {
+                    log::warn!(
+                        "{}は既に{}に割り当てられているため無視しました。",
+                        code,
+                        other.label()
+                    );
+                    continue;
+                }
+                input_map.bindings.push((code.to_string(), action));
+            }
+        }
+        let default = InputMap::default();
+        for action in Action::ALL {
+            if input_map.codes(action).is_empty() {
+                for code in default.codes(action) {
+                    if input_map.action(code).is_none() {
+                        input_map.bindings.push((code.to_string(), action));
+                    }
+                }
+            }
+        }
+        input_map
+    }
+    // キーに割り当てられた操作
+    pub fn action(&self, code: &str) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound == code)
+            .map(|(_, action)| *action)
+    }
+    // 画面に表示する、操作に割り当てた最初のキーの名前("KeyX"なら"X")
+    pub fn key_label(&self, action: Action) -> String {
+        self.codes(action)
+            .first()
+            .map(|code| {
+                code.trim_start_matches("Key")
+                    .trim_start_matches("Digit")
+                    .to_string()
+            })
+            .unwrap_or_default()
+    }
+    // 操作に割り当てられたキーの一覧
+    pub fn codes(&self, action: Action) -> Vec<&str> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(code, _)| code.as_str())
+            .collect()
+    }
+    // 操作に割り当てるキーをcodeのみに変更して保存する
+    // codeが他の操作に割り当てられていれば変更せず、その操作をErrで返す
+    pub fn bind(&mut self, action: Action, code: &str) -> Result<(), Action> {
+        match self.action(code) {
+            Some(other) if other != action => return Err(other),
+            _ => (),
+        }
+        self.bindings.retain(|(_, bound)| *bound != action);
+        self.bindings.push((code.to_string(), action));
+        self.save();
+        Ok(())
+    }
+    // 初期設定に戻して保存する
+    pub fn reset(&mut self) {
+        *self = InputMap::default();
+        self.save();
+    }
+}
+
+// 対応する操作のキーやボタンが押されている時真
+#[derive(Debug, Default)]
 pub struct KeyDown {
     pub left: bool,  // プレイヤーを左へ移動させる
     pub right: bool, // プレイヤーを右へ移動させる
     pub shot: bool,  // プレイヤーが弾を撃つ
     pub pause: bool, // ポーズする
+    // 2人同時プレイでの2人目のプレイヤーの操作
+    pub left_2: bool,
+    pub right_2: bool,
+    pub shot_2: bool,
+    pub difficulty: bool, // 難易度を切り替える
+}
+
+impl KeyDown {
+    pub fn set(&mut self, action: Action, pressed: bool) {
+        let button = match action {
+            Action::Left => &mut self.left,
+            Action::Right => &mut self.right,
+            Action::Shot => &mut self.shot,
+            Action::Pause => &mut self.pause,
+            Action::Left2 => &mut self.left_2,
+            Action::Right2 => &mut self.right_2,
+            Action::Shot2 => &mut self.shot_2,
+            Action::Difficulty => &mut self.difficulty,
+            // 全画面表示の切り替えは押した時に行うため、押している状態は持たない
+            Action::Fullscreen => return,
+        };
+        *button = pressed;
+    }
+    // 現在のキー入力状態を1フレーム分の入力として取り出す
+    pub fn frame(&self) -> InputFrame {
+        InputFrame {
+            left: self.left,
+            right: self.right,
+            shot: self.shot,
+            pause: self.pause,
+            left_2: self.left_2,
+            right_2: self.right_2,
+            shot_2: self.shot_2,
+            difficulty: self.difficulty,
+        }
+    }
 }
 
-pub fn input_setup(input_key: &Rc<RefCell<KeyDown>>) {
+// キーボードの入力状態とキーの割り当て
+pub struct Keyboard {
+    pub key_down: KeyDown,
+    pub input_map: InputMap,
+    pub rebinding: Option<Action>, // キー割り当ての変更で、次に押されたキーを割り当てる操作
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Keyboard {
+            key_down: KeyDown::default(),
+            input_map: InputMap::load(),
+            rebinding: None,
+        }
+    }
+}
+
+// キー割り当ての変更が終わるとon_rebindに結果を渡す
+// 他の操作に割り当て済みのキーが押された場合はErr(その操作)
+pub fn input_setup(keyboard: &Rc<RefCell<Keyboard>>, on_rebind: Callback<Result<(), Action>>) {
     // キー入力処理
-    let key_down = Rc::clone(&input_key);
+    let key_down = Rc::clone(keyboard);
     let document = web_sys::window().unwrap().document().unwrap();
     let body = document.body().unwrap();
     let canvas = document
@@ -26,7 +292,25 @@ pub fn input_setup(input_key: &Rc<RefCell<KeyDown>>) {
     // キー押し下げ
 
     let closure_key_down = Closure::wrap(Box::new(move |e: web_sys::KeyboardEvent| {
-        function_key_down(e, &mut key_down.borrow_mut());
+        // 入力欄での文字入力はゲームの操作にも割り当ての変更にも使わない
+        if in_text_field(&e) {
+            return;
+        }
+        let keyboard = &mut *key_down.borrow_mut();
+        // 割り当ての変更中は、押されたキーを割り当てるのみ
+        if let Some(action) = keyboard.rebinding.take() {
+            e.prevent_default();
+            on_rebind.emit(keyboard.input_map.bind(action, &e.code()));
+            return;
+        }
+        match keyboard.input_map.action(&e.code()) {
+            // 全画面表示は押した時に切り替える(キーリピートは除く)
+            Some(Action::Fullscreen) if !e.repeat() => {
+                viewport::toggle_fullscreen();
+            }
+            Some(action) => keyboard.key_down.set(action, true),
+            None => (),
+        }
     }) as Box<dyn FnMut(_)>);
 
     body.add_event_listener_with_callback("keydown", closure_key_down.as_ref().unchecked_ref())
@@ -34,48 +318,148 @@ pub fn input_setup(input_key: &Rc<RefCell<KeyDown>>) {
     closure_key_down.forget();
 
     // キー押し上げ
-    let key_up = Rc::clone(&input_key);
+    let key_up = Rc::clone(keyboard);
     let closure_key_up = Closure::wrap(Box::new(move |e: web_sys::KeyboardEvent| {
-        function_key_up(e, &mut key_up.borrow_mut());
+        let keyboard = &mut *key_up.borrow_mut();
+        if let Some(action) = keyboard.input_map.action(&e.code()) {
+            keyboard.key_down.set(action, false);
+        }
     }) as Box<dyn FnMut(_)>);
     body.add_event_listener_with_callback("keyup", closure_key_up.as_ref().unchecked_ref())
         .unwrap();
     closure_key_up.forget();
 }
-// キー押し下げ時に呼び出し
-fn function_key_down(event: web_sys::KeyboardEvent, input_data: &mut KeyDown) {
-    match &*event.key() {
-        "ArrowLeft" | "a" => {
-            input_data.left = true;
-        }
-        "ArrowRight" | "d" => {
-            input_data.right = true;
-        }
-        "Space" | "Enter" | "z" => {
-            input_data.shot = true;
-        }
-        "Escape" => {
-            input_data.pause = true;
-        }
-        _ => (),
+
+// 文字や数値の入力欄でのキー入力ならば真
+// チェックボックスやスライダーはフォーカスがあってもゲームの操作を受け付ける
+fn in_text_field(e: &web_sys::KeyboardEvent) -> bool {
+    let Some(element) = e
+        .target()
+        .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+    else {
+        return false;
     };
+    match element.dyn_ref::<HtmlInputElement>() {
+        Some(input) => !matches!(
+            input.type_().as_str(),
+            "checkbox" | "radio" | "range" | "button" | "file"
+        ),
+        None => element.tag_name() == "TEXTAREA",
+    }
 }
 
-// キー押し上げ時に呼び出し
-fn function_key_up(event: web_sys::KeyboardEvent, input_data: &mut KeyDown) {
-    match &*event.key() {
-        "ArrowLeft" | "a" => {
-            input_data.left = false;
-        }
-        "ArrowRight" | "d" => {
-            input_data.right = false;
-        }
-        "Space" | "Enter" | "z" => {
-            input_data.shot = false;
+// 標準配置のゲームパッドのボタン番号
+const BUTTON_FACE: [usize; 4] = [0, 1, 2, 3]; // A、B、X、Y
+const BUTTON_SELECT: usize = 8;
+const BUTTON_START: usize = 9;
+const BUTTON_DPAD_LEFT: usize = 14;
+const BUTTON_DPAD_RIGHT: usize = 15;
+// スティックの遊び(傾きの大きさ0〜1)の初期値
+const DEFAULT_DEADZONE: f64 = 0.3;
+
+// ゲームパッドの入力
+// 接続された順に1人目、2人目(2人同時プレイ)のプレイヤーの操作に割り当てる
+pub struct Gamepads {
+    pub deadzone: f64,                // スティックの傾きがこれ以下なら入力なしとみなす
+    connected: Rc<RefCell<Vec<u32>>>, // 接続中のゲームパッドの番号(接続された順)
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Gamepads {
+            deadzone: DEFAULT_DEADZONE,
+            connected: Rc::new(RefCell::new(Vec::new())),
         }
-        "Escape" => {
-            input_data.pause = false;
+    }
+    // ゲームパッドの抜き差しを監視する
+    pub fn setup(&self) {
+        let window = web_sys::window().unwrap();
+
+        let connected = Rc::clone(&self.connected);
+        let closure_connected = Closure::wrap(Box::new(move |e: web_sys::GamepadEvent| {
+            if let Some(gamepad) = e.gamepad() {
+                log::info!("ゲームパッドが接続されました: {}", gamepad.id());
+                let mut connected = connected.borrow_mut();
+                if !connected.contains(&gamepad.index()) {
+                    connected.push(gamepad.index());
+                }
+            }
+        }) as Box<dyn FnMut(_)>);
+        window
+            .add_event_listener_with_callback(
+                "gamepadconnected",
+                closure_connected.as_ref().unchecked_ref(),
+            )
+            .unwrap();
+        closure_connected.forget();
+
+        let connected = Rc::clone(&self.connected);
+        let closure_disconnected = Closure::wrap(Box::new(move |e: web_sys::GamepadEvent| {
+            if let Some(gamepad) = e.gamepad() {
+                log::info!("ゲームパッドが切断されました: {}", gamepad.id());
+                connected
+                    .borrow_mut()
+                    .retain(|index| *index != gamepad.index());
+            }
+        }) as Box<dyn FnMut(_)>);
+        window
+            .add_event_listener_with_callback(
+                "gamepaddisconnected",
+                closure_disconnected.as_ref().unchecked_ref(),
+            )
+            .unwrap();
+        closure_disconnected.forget();
+    }
+    // 現在のゲームパッドの状態を1フレーム分の入力として取り出す
+    pub fn poll(&self) -> InputFrame {
+        let mut input = InputFrame::default();
+        let Some(gamepads) =
+            web_sys::window().and_then(|window| window.navigator().get_gamepads().ok())
+        else {
+            return input;
+        };
+        for (player, index) in self.connected.borrow().iter().take(2).enumerate() {
+            let Ok(gamepad) = gamepads.get(*index).dyn_into::<web_sys::Gamepad>() else {
+                continue;
+            };
+            let buttons: Vec<bool> = gamepad
+                .buttons()
+                .iter()
+                .map(|button| {
+                    button
+                        .dyn_into::<web_sys::GamepadButton>()
+                        .is_ok_and(|button| button.pressed())
+                })
+                .collect();
+            let axis_x = gamepad.axes().get(0).as_f64().unwrap_or(0.);
+            let pad = pad_input(&buttons, axis_x, self.deadzone);
+            if player == 0 {
+                input = input.merge(pad);
+            } else {
+                // 2台目は2人目のプレイヤーの操作とする(ポーズと難易度の切り替えは共通)
+                input = input.merge(InputFrame {
+                    pause: pad.pause,
+                    difficulty: pad.difficulty,
+                    left_2: pad.left,
+                    right_2: pad.right,
+                    shot_2: pad.shot,
+                    ..Default::default()
+                });
+            }
         }
-        _ => (),
-    };
+        input
+    }
+}
+
+// ゲームパッド1台分のボタンとスティックの状態を入力に変換する
+fn pad_input(buttons: &[bool], axis_x: f64, deadzone: f64) -> InputFrame {
+    let pressed = |i: usize| buttons.get(i).copied().unwrap_or(false);
+    InputFrame {
+        left: pressed(BUTTON_DPAD_LEFT) || axis_x < -deadzone,
+        right: pressed(BUTTON_DPAD_RIGHT) || axis_x > deadzone,
+        shot: BUTTON_FACE.iter().any(|i| pressed(*i)),
+        pause: pressed(BUTTON_START),
+        difficulty: pressed(BUTTON_SELECT),
+        ..Default::default()
+    }
 }
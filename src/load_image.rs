@@ -1,13 +1,15 @@
-use crate::dot_data::Color;
 use std::collections::HashMap;
 use wasm_bindgen::{Clamped, JsCast, JsValue};
 use web_sys::{window, ImageBitmap, ImageData};
 
 use crate::dot_data;
+use crate::sprite_pack::SpritePack;
+use crate::theme::{Color, Theme};
 
 #[derive(Eq, Hash, PartialEq, Clone)]
 pub enum ImageType {
     Player,
+    Player2, // 2人同時プレイでの2人目のプレイヤー
     OctopusOpen,
     OctopusClose,
     CrabBanzai,
@@ -20,15 +22,12 @@ pub enum ImageType {
     ExplosionPurple,
     ExpolsionGreen,
     LandPlayerBulletFront,
-    LandPlayerBulletShadow,
-    Torchika,
     Ufo,
     UfoExplosion,
     EnemyBulletSquiggly,
     EnemyBulletPlunger,
     EnemyBulletRolling,
     EnemyBulletExplosionFront,
-    EnemyBulletExplosionShadow,
     PlayerExplosion1,
     PlayerExplosion2,
 }
@@ -37,6 +36,7 @@ impl ImageType {
     pub fn ret_all_types() -> Vec<ImageType> {
         vec![
             ImageType::Player,
+            ImageType::Player2,
             ImageType::CrabBanzai,
             ImageType::CrabDown,
             ImageType::OctopusOpen,
@@ -48,109 +48,90 @@ impl ImageType {
             ImageType::ExplosionPurple,
             ImageType::ExpolsionGreen,
             ImageType::LandPlayerBulletFront,
-            ImageType::LandPlayerBulletShadow,
-            ImageType::Torchika,
             ImageType::Ufo,
             ImageType::UfoExplosion,
             ImageType::EnemyBulletSquiggly,
             ImageType::EnemyBulletPlunger,
             ImageType::EnemyBulletRolling,
             ImageType::EnemyBulletExplosionFront,
-            ImageType::EnemyBulletExplosionShadow,
             ImageType::PlayerExplosion1,
             ImageType::PlayerExplosion2,
         ]
     }
+    // 元にするドットマップの名前と塗る色
+    pub fn source(&self) -> (&'static str, Color) {
+        match self {
+            ImageType::Player => ("player", Color::Turquoise),
+            ImageType::Player2 => ("player", Color::Orange),
+            ImageType::CrabBanzai => ("crab_banzai", Color::Turquoise),
+            ImageType::CrabDown => ("crab_down", Color::Turquoise),
+            ImageType::OctopusOpen => ("octopus_open", Color::Purple),
+            ImageType::OctopusClose => ("octopus_close", Color::Purple),
+            ImageType::SquidOpen => ("squid_open", Color::Green),
+            ImageType::SquidClose => ("squid_close", Color::Green),
+            ImageType::PlayerBullet => ("player_bullet", Color::PlayerBullet),
+            ImageType::ExplosionTurquoise => ("explosion", Color::Turquoise),
+            ImageType::ExplosionPurple => ("explosion", Color::Purple),
+            ImageType::ExpolsionGreen => ("explosion", Color::Green),
+            ImageType::LandPlayerBulletFront => ("land_player_bullet", Color::Red),
+            ImageType::Ufo => ("ufo", Color::Purple),
+            ImageType::UfoExplosion => ("ufo_explosion", Color::Purple),
+            ImageType::EnemyBulletSquiggly => ("enemy_bullet_squiggly", Color::Yellow),
+            ImageType::EnemyBulletPlunger => ("enemy_bullet_plunger", Color::Yellow),
+            ImageType::EnemyBulletRolling => ("enemy_bullet_rolling", Color::Yellow),
+            ImageType::EnemyBulletExplosionFront => ("enemy_bullet_explosion", Color::Red),
+            ImageType::PlayerExplosion1 => ("player_explosion_1", Color::Red),
+            ImageType::PlayerExplosion2 => ("player_explosion_2", Color::Red),
+        }
+    }
 }
 // すべての画像のImageDataをまとめて返す
-pub fn image_data_collect() -> (HashMap<ImageType, ImageData>, Vec<Vec<u8>>) {
+// sprite_packに含まれる画像は組み込みのドットマップの代わりに使い、themeの配色で塗る
+pub fn image_data_collect(
+    sprite_pack: &SpritePack,
+    theme: &Theme,
+) -> (HashMap<ImageType, ImageData>, Vec<Vec<u8>>) {
     let mut all_image_list = ImageDataList {
         image_data_list: HashMap::new(),
         image_rgba_list: Vec::new(),
+        sprite_pack,
+        theme,
     };
-    all_image_list.ret_image_data("player", ImageType::Player, Color::Turquoise);
-    all_image_list.ret_image_data("crab_banzai", ImageType::CrabBanzai, Color::Turquoise);
-    all_image_list.ret_image_data("crab_down", ImageType::CrabDown, Color::Turquoise);
-    all_image_list.ret_image_data("octopus_open", ImageType::OctopusOpen, Color::Purple);
-    all_image_list.ret_image_data("octopus_close", ImageType::OctopusClose, Color::Purple);
-    all_image_list.ret_image_data("squid_open", ImageType::SquidOpen, Color::Green);
-    all_image_list.ret_image_data("squid_close", ImageType::SquidClose, Color::Green);
-    all_image_list.ret_image_data(
-        "player_bullet",
-        ImageType::PlayerBullet,
-        Color::PlayerBullet,
-    );
-
-    all_image_list.ret_image_data("explosion", ImageType::ExplosionTurquoise, Color::Turquoise);
-    all_image_list.ret_image_data("explosion", ImageType::ExplosionPurple, Color::Purple);
-    all_image_list.ret_image_data("explosion", ImageType::ExpolsionGreen, Color::Green);
-
-    all_image_list.ret_image_data(
-        "land_player_bullet",
-        ImageType::LandPlayerBulletFront,
-        Color::Red,
-    );
-    all_image_list.ret_image_data(
-        "land_player_bullet",
-        ImageType::LandPlayerBulletShadow,
-        Color::Background,
-    );
-
-    all_image_list.ret_image_data("torchika", ImageType::Torchika, Color::Red);
-    all_image_list.ret_image_data("ufo", ImageType::Ufo, Color::Purple);
-    all_image_list.ret_image_data("ufo_explosion", ImageType::UfoExplosion, Color::Purple);
-
-    all_image_list.ret_image_data(
-        "enemy_bullet_squiggly",
-        ImageType::EnemyBulletSquiggly,
-        Color::Yellow,
-    );
-    all_image_list.ret_image_data(
-        "enemy_bullet_plunger",
-        ImageType::EnemyBulletPlunger,
-        Color::Yellow,
-    );
-    all_image_list.ret_image_data(
-        "enemy_bullet_rolling",
-        ImageType::EnemyBulletRolling,
-        Color::Yellow,
-    );
-    all_image_list.ret_image_data(
-        "enemy_bullet_explosion",
-        ImageType::EnemyBulletExplosionFront,
-        Color::Red,
-    );
-    all_image_list.ret_image_data(
-        "enemy_bullet_explosion",
-        ImageType::EnemyBulletExplosionShadow,
-        Color::Background,
-    );
-    all_image_list.ret_image_data(
-        "player_explosion_1",
-        ImageType::PlayerExplosion1,
-        Color::Red,
-    );
-    all_image_list.ret_image_data(
-        "player_explosion_2",
-        ImageType::PlayerExplosion2,
-        Color::Red,
-    );
+    for image_type in ImageType::ret_all_types() {
+        let (name, color) = image_type.source();
+        all_image_list.ret_image_data(name, image_type, color);
+    }
     (
         all_image_list.image_data_list,
         all_image_list.image_rgba_list,
     )
 }
 
-struct ImageDataList {
+struct ImageDataList<'a> {
     image_data_list: HashMap<ImageType, ImageData>,
     // ダングリング防止のため、対応するImageDataがある間は保存する
     image_rgba_list: Vec<Vec<u8>>,
+    sprite_pack: &'a SpritePack,
+    theme: &'a Theme,
 }
 
-impl ImageDataList {
+impl ImageDataList<'_> {
     fn ret_image_data(&mut self, name: &str, image_type: ImageType, color: Color) {
-        let image_dot = dot_data::ret_dot_data(name);
-        let image_rgba = image_dot.create_color_dot_map(color);
+        let color = self.theme.rgba(color);
+        let mut image_dot = self.sprite_pack.shape(name);
+        let image_rgba = match image_dot.create_color_dot_map(color) {
+            Ok(image_rgba) => image_rgba,
+            Err(err) => {
+                // 画像パックの画像が使えなければ組み込みの画像に戻す
+                log::warn!(
+                    "{}の画像を作れないため組み込みの画像を使います: {:#}",
+                    name,
+                    err
+                );
+                image_dot = dot_data::ret_dot_data(name);
+                image_dot.create_color_dot_map(color).unwrap()
+            }
+        };
         let image_data = ImageData::new_with_u8_clamped_array_and_sh(
             Clamped(&image_rgba),
             image_dot.width,
@@ -172,5 +153,5 @@ pub async fn imagedata2bitmap(image_data: ImageData) -> Result<ImageBitmap, JsVa
         .await
         .unwrap();
 
-    Ok(result.dyn_into::<ImageBitmap>()?)
+    result.dyn_into::<ImageBitmap>()
 }
@@ -1,38 +1,58 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use wasm_bindgen::{prelude::Closure, JsCast, JsValue};
-use web_sys::{window, ImageBitmap, ImageData};
+use wasm_bindgen::{prelude::Closure, JsCast};
+use web_sys::{window, File, HtmlElement, HtmlInputElement, ImageBitmap, ImageData};
 use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+use web_sys::{HtmlSelectElement, HtmlTextAreaElement};
 use yew::prelude::*;
 
-use enemy::*;
+use cellophane::Cellophane;
+use difficulty::Difficulty;
+use enemy::EnemyType;
+use game::{GameState, PlayMode};
+use high_score::{LocalStorage, MemoryStorage};
 use load_image::ImageType;
-use pause::Pause;
-use player::Player;
+use renderer::CanvasRenderer;
+use replay::{Playback, Replay};
 use sound::Audio;
+use sprite_editor::{SpriteEdit, SpriteEditor};
+use sprite_pack::SpritePack;
+use stage::StageList;
+use stage_editor::{StageEdit, StageEditor};
+use theme::{Theme, ThemePreset};
+use timestep::FixedTimestep;
 use title::Title;
-use ufo::Ufo;
+use viewport::ScaleMode;
 
+mod cellophane;
+mod checksum;
+mod difficulty;
 mod dot_data;
 mod enemy;
+#[cfg(test)]
+mod framebuffer;
+mod game;
+mod high_score;
 mod input;
 mod load_image;
 mod math;
-mod pause;
-mod pixel_ctrl;
 mod player;
+mod render;
+mod renderer;
+mod replay;
+mod rng;
 mod sound;
+mod sprite_editor;
+mod sprite_pack;
+mod stage;
+mod stage_editor;
+mod theme;
+mod timestep;
 mod title;
+mod torchika;
 mod ufo;
-
-enum Scene {
-    Title,            // タイトル画面
-    Pause,            // 一時停止状態
-    Play,             // ゲーム実行中
-    LaunchStage(i32), // ゲーム開始後、プレイヤーが操作可能になるまで
-    GameOver(i32),
-}
+mod viewport;
 
 pub enum Msg {
     // ビットマップ画像を取得
@@ -51,25 +71,63 @@ pub enum Msg {
     AudioVolumeReset,
     ResetCanvas,
     Initialize,
-    MainLoop,
+    PlayReplay,
+    DownloadReplay,
+    LoadReplayFile(File),
+    LoadReplay(anyhow::Result<Vec<u8>>),
+    MainLoop(f64), // requestAnimationFrameから渡された時刻 [ms]
+    SetDeadzone(f64),
+    SetSemiAuto(bool),
+    SetTheme(ThemePreset),                 // 配色を変更してすぐに描き直す
+    SetCellophane(bool),                   // セロハンで色を付ける描き方に切り替える
+    SetCellophaneBand(usize, f64, f64),    // セロハンを貼る範囲の上端と下端
+    SetScaleMode(ScaleMode),               // 画面を拡大する方法
+    Resize,                                // ウィンドウに合わせてキャンバスの表示サイズを決め直す
+    StartRebind(input::Action),            // 次に押されたキーを操作に割り当てる
+    KeyRebound(Result<(), input::Action>), // 割り当ての結果(Errは既にそのキーを使っている操作)
+    ResetKeyBindings,
+    EditStage(StageEdit), // ステージエディタでの編集
+    PreviewStage,         // 編集中のステージの動きをタイトル画面で確認する
+    ApplyStages,          // 編集したステージ定義を次のゲームから使う
+    ExportStages,         // 編集したステージ定義をファイルとして保存する
+    ImportStagesFile(File),
+    ImportStages(anyhow::Result<Vec<u8>>),
+    ResetStages,            // 組み込みのステージ定義に戻す
+    EditSprite(SpriteEdit), // ドット絵エディタでの編集
+    SaveSprites,            // 編集した画像をゲームの描画に使い、ブラウザに保存する
+    ClearSprites,           // 保存した画像を消す
+    // 起動時に取得した画像パック
+    SpritePackFetched(anyhow::Result<Option<String>>),
+    LoadSpritePackFile(File),
+    LoadSpritePack(anyhow::Result<Vec<u8>>),
+    DownloadSpritePack, // 編集中の画像を画像パックとして保存する
+    DropFile(File),     // ページにドロップされたファイルを種類に応じて読み込む
 }
 
 struct AnimationCanvas {
     canvas: NodeRef,
-    player: Player,
-    enemy_manage: EnemyManage,
-    torchika: Option<ImageBitmap>,
-    ufo: Ufo,
+    game: GameState,
+    images: HashMap<ImageType, ImageBitmap>,
     audio: Audio,
-    stage_number: usize, // 最初は1、最終は9
-    callback: Closure<dyn FnMut()>,
-    input_key_down: Rc<RefCell<input::KeyDown>>,
-    need_to_screen_init: bool, // 真ならば画面全体の初期化が必要
-    new_game: bool,            // 真ならば残機、点数などをすべてリセットする
-    canvas_width: f64,
-    canvas_height: f64,
-    pause: Pause,
-    scene: Scene,
+    callback: Closure<dyn FnMut(f64)>,
+    timestep: FixedTimestep,
+    keyboard: Rc<RefCell<input::Keyboard>>,
+    rebind_error: Option<String>, // キー割り当ての変更に失敗した理由
+    input_touch: Rc<RefCell<input::KeyDown>>, // 画面下のボタンへのタッチ
+    gamepads: input::Gamepads,
+    semi_auto: bool,   // セミオート射撃の設定(リプレイ再生中も保持する)
+    stages: StageList, // 適用したステージ定義(リプレイ再生中も保持する)
+    stage_editor: StageEditor,
+    site_sprite_pack: SpritePack, // 起動時に取得した画像パック
+    sprite_pack: SpritePack, // 読み込んだり編集したりして保存した画像パック(取得したものより優先する)
+    sprite_editor: SpriteEditor,
+    sprite_canvas: NodeRef,    // ドット絵エディタのキャンバス
+    theme_preset: ThemePreset, // 選んだ配色
+    theme: Theme,
+    cellophane: Cellophane,
+    screen: Option<HtmlCanvasElement>, // セロハンを貼る前の画面
+    scale_mode: ScaleMode,             // 画面を拡大する方法
+    playback: Option<Playback>,        // 再生中はキー入力の代わりに記録した入力を使う
     title: Title,
 }
 
@@ -77,42 +135,47 @@ impl Component for AnimationCanvas {
     type Properties = ();
     type Message = Msg;
     fn create(ctx: &Context<Self>) -> Self {
-        // 使用する画像のImageDataとその参照元の配列を取得
-        let (image_data_list, image_rgb_list) = load_image::image_data_collect();
-        // ビットマップ形式に変換
+        // 画像パックの取得を待たずに組み込みの画像で描けるようにし、取得できたら作り直す
         ctx.link().send_future(async {
-            Msg::RetBitmapImage(ImageType::ret_all_types(), image_data_list, image_rgb_list)
+            Msg::SpritePackFetched(sprite_pack::fetch(sprite_pack::SPRITE_PACK_URL).await)
         });
+        let sprite_pack = SpritePack::load();
+        let theme_preset = ThemePreset::load();
 
         let comp_ctx = ctx.link().clone();
         let callback = Closure::wrap(
-            Box::new(move || comp_ctx.send_message(Msg::MainLoop)) as Box<dyn FnMut()>
+            Box::new(move |now| comp_ctx.send_message(Msg::MainLoop(now))) as Box<dyn FnMut(f64)>,
         );
 
-        Self {
+        let component = Self {
             canvas: NodeRef::default(),
-            // まだ画像が未取得なので、仮の値を入れる
-            player: Player::empty(),
-            enemy_manage: EnemyManage::default(),
-            torchika: None,
+            // まだキャンバスが未取得なので、仮の値を入れる
+            game: GameState::new(0., 0., 0, Box::new(MemoryStorage::default())),
+            images: HashMap::new(),
             callback,
-            ufo: Ufo::empty(),
+            timestep: FixedTimestep::new(),
             audio: Audio::new(),
-            input_key_down: Rc::new(RefCell::new(input::KeyDown {
-                left: false,
-                right: false,
-                shot: false,
-                pause: false,
-            })),
-            need_to_screen_init: true,
-            new_game: true,
-            stage_number: 1,
-            canvas_width: 0.,
-            canvas_height: 0.,
-            pause: Pause::new(),
+            keyboard: Rc::new(RefCell::new(input::Keyboard::new())),
+            rebind_error: None,
+            input_touch: Rc::new(RefCell::new(input::KeyDown::default())),
+            gamepads: input::Gamepads::new(),
+            semi_auto: false,
+            stages: StageList::default(),
+            stage_editor: StageEditor::new(StageList::default()),
+            sprite_editor: SpriteEditor::new(&sprite_pack),
+            site_sprite_pack: SpritePack::default(),
+            sprite_pack,
+            sprite_canvas: NodeRef::default(),
+            theme_preset,
+            theme: theme_preset.theme(),
+            cellophane: Cellophane::load(),
+            screen: None,
+            scale_mode: ScaleMode::load(),
+            playback: None,
             title: Title::new(0., 0.),
-            scene: Scene::Title,
-        }
+        };
+        component.reload_images(ctx);
+        component
     }
 
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
@@ -146,30 +209,7 @@ impl Component for AnimationCanvas {
                 _image_rgb,
                 (image_type, image_bitmap),
             ) => {
-                match image_type {
-                    ImageType::Player => self.player.image_front = Some(image_bitmap),
-                    ImageType::PlayerBullet => self.player.bullet.image_front = Some(image_bitmap),
-                    ImageType::LandPlayerBulletFront => {
-                        self.player.bullet.image_land_front = Some(image_bitmap)
-                    }
-                    ImageType::LandPlayerBulletShadow => {
-                        self.player.bullet.image_land_shadow = Some(image_bitmap)
-                    }
-                    ImageType::PlayerExplosion1 => {
-                        self.player.image_explosion_1 = Some(image_bitmap)
-                    }
-                    ImageType::PlayerExplosion2 => {
-                        self.player.image_explosion_2 = Some(image_bitmap)
-                    }
-                    ImageType::Torchika => self.torchika = Some(image_bitmap),
-                    ImageType::Ufo => self.ufo.image = Some(image_bitmap),
-                    ImageType::UfoExplosion => self.ufo.explosion.image = Some(image_bitmap),
-                    _ => {
-                        self.enemy_manage
-                            .images_list
-                            .insert(image_type, image_bitmap);
-                    }
-                }
+                self.images.insert(image_type, image_bitmap);
                 ctx.link().send_message(Msg::RetBitmapImage(
                     remain_image_type_list,
                     image_data_list,
@@ -190,36 +230,38 @@ impl Component for AnimationCanvas {
             // 初期化
             Msg::Initialize => {
                 let canvas: HtmlCanvasElement = self.canvas.cast().unwrap();
-                (self.canvas_width, self.canvas_height) =
-                    (canvas.width() as f64, canvas.height() as f64);
-                self.title = Title::new(self.canvas_width, self.canvas_height);
-                // 敵インベーダーの初期化
-                self.enemy_manage
-                    .register_enemys(self.canvas_width, self.canvas_height);
-                // プレイヤーの初期化
-                self.player = Player::new(
-                    self.canvas_width,
-                    self.canvas_height,
-                    self.player.image_front.clone().unwrap(),
-                    self.player.bullet.image_front.clone().unwrap(),
-                    self.player.bullet.image_land_front.clone().unwrap(),
-                    self.player.bullet.image_land_shadow.clone().unwrap(),
-                    self.player.image_explosion_1.clone().unwrap(),
-                    self.player.image_explosion_2.clone().unwrap(),
-                );
-                self.ufo = Ufo::new(
-                    self.ufo.image.clone().unwrap(),
-                    self.ufo.explosion.image.clone().unwrap(),
+                // ゲームは表示サイズによらず論理的な解像度で動かす
+                let (canvas_width, canvas_height) = (
+                    viewport::LOGICAL_WIDTH as f64,
+                    viewport::LOGICAL_HEIGHT as f64,
                 );
+                self.title = Title::new(canvas_width, canvas_height);
+                self.update_title_keys();
+                self.game = new_game_state(canvas_width, canvas_height);
+                self.game.stages = self.stages.clone();
+                // セロハンを貼る前の画面は表示しないキャンバスに描く
+                let screen: HtmlCanvasElement = window()
+                    .unwrap()
+                    .document()
+                    .unwrap()
+                    .create_element("canvas")
+                    .unwrap()
+                    .unchecked_into();
+                screen.set_width(canvas.width());
+                screen.set_height(canvas.height());
+                self.screen = Some(screen);
                 // キー入力情報初期化
-                input::input_setup(&self.input_key_down);
+                input::input_setup(&self.keyboard, ctx.link().callback(Msg::KeyRebound));
+                self.gamepads.setup();
+                viewport::setup(ctx.link().callback(|_| Msg::Resize));
+                viewport::layout(&canvas, self.scale_mode);
 
                 ctx.link().send_message(Msg::RetAudio);
                 true
             }
             // 音データを取得
             Msg::RetAudio => {
-                if self.audio.invader_move.len() == 0 {
+                if self.audio.invader_move.is_empty() {
                     ctx.link()
                         .send_future(async { Msg::RegisterAudio(sound::ret_audio().await) });
                 }
@@ -228,7 +270,11 @@ impl Component for AnimationCanvas {
             // 音データを保存
             Msg::RegisterAudio(audio) => {
                 self.audio = audio;
-                ctx.link().send_message(Msg::MainLoop);
+                // 最初のフレームは次の描画タイミングで開始する
+                window()
+                    .unwrap()
+                    .request_animation_frame(self.callback.as_ref().unchecked_ref())
+                    .unwrap();
                 false
             }
             Msg::AudioVolumeUp => {
@@ -243,9 +289,252 @@ impl Component for AnimationCanvas {
                 self.audio.reset_volume();
                 false
             }
+            // 最後に終了したゲームを再生
+            Msg::PlayReplay => {
+                if let Some(replay) = self.game.last_replay.clone() {
+                    self.start_playback(replay);
+                }
+                false
+            }
+            // 最後に終了したゲームのリプレイファイルを保存
+            Msg::DownloadReplay => {
+                if let Some(replay) = &self.game.last_replay {
+                    replay::download(replay);
+                }
+                false
+            }
+            // 選択されたリプレイファイルを読み込む
+            Msg::LoadReplayFile(file) => {
+                ctx.link()
+                    .send_future(async move { Msg::LoadReplay(replay::read_file(&file).await) });
+                false
+            }
+            Msg::LoadReplay(bytes) => {
+                match bytes.and_then(|bytes| Replay::from_bytes(&bytes)) {
+                    Ok(replay) => self.start_playback(replay),
+                    Err(err) => alert(&format!("リプレイファイルを読み込めません: {}", err)),
+                }
+                false
+            }
             // ループ
-            Msg::MainLoop => {
-                self.main_loop();
+            Msg::MainLoop(now) => {
+                self.main_loop(now);
+                false
+            }
+            // ゲームパッドのスティックの遊びを変更
+            Msg::SetDeadzone(deadzone) => {
+                self.gamepads.deadzone = deadzone;
+                true
+            }
+            // 射撃の方式を変更(次のゲームから有効)
+            Msg::SetSemiAuto(semi_auto) => {
+                self.semi_auto = semi_auto;
+                if self.playback.is_none() {
+                    self.game.semi_auto = semi_auto;
+                }
+                true
+            }
+            Msg::SetTheme(theme_preset) => {
+                self.theme_preset = theme_preset;
+                self.theme = theme_preset.theme();
+                theme_preset.save();
+                self.reload_images(ctx);
+                true
+            }
+            Msg::SetCellophane(enabled) => {
+                self.cellophane.enabled = enabled;
+                self.cellophane.save();
+                self.reload_images(ctx);
+                true
+            }
+            Msg::SetCellophaneBand(index, top, bottom) => {
+                self.cellophane.set_band(index, top, bottom);
+                self.cellophane.save();
+                true
+            }
+            Msg::SetScaleMode(scale_mode) => {
+                self.scale_mode = scale_mode;
+                scale_mode.save();
+                ctx.link().send_message(Msg::Resize);
+                true
+            }
+            Msg::Resize => {
+                if let Some(canvas) = self.canvas.cast::<HtmlCanvasElement>() {
+                    viewport::layout(&canvas, self.scale_mode);
+                }
+                false
+            }
+            // 同じ操作をもう一度選んだ場合は取りやめる
+            Msg::StartRebind(action) => {
+                let mut keyboard = self.keyboard.borrow_mut();
+                keyboard.rebinding = if keyboard.rebinding == Some(action) {
+                    None
+                } else {
+                    Some(action)
+                };
+                self.rebind_error = None;
+                true
+            }
+            Msg::KeyRebound(result) => {
+                blur_active_element();
+                self.rebind_error = result.err().map(|other| {
+                    format!("そのキーは既に{}に割り当てられています。", other.label())
+                });
+                self.update_title_keys();
+                true
+            }
+            Msg::ResetKeyBindings => {
+                {
+                    let mut keyboard = self.keyboard.borrow_mut();
+                    keyboard.input_map.reset();
+                    keyboard.rebinding = None;
+                }
+                self.rebind_error = None;
+                self.update_title_keys();
+                true
+            }
+            Msg::EditStage(edit) => {
+                self.stage_editor.edit(edit);
+                true
+            }
+            Msg::PreviewStage => {
+                blur_active_element();
+                let started =
+                    self.playback.is_none() && self.game.start_preview(self.stage_editor.stage());
+                self.stage_editor.message =
+                    (!started).then(|| "プレビューはタイトル画面でのみ表示できます。".to_string());
+                true
+            }
+            Msg::ApplyStages => {
+                self.stage_editor.message = Some(match self.stage_editor.stages.validate() {
+                    Ok(()) => {
+                        self.stages = self.stage_editor.stages.clone();
+                        if self.playback.is_none() {
+                            self.game.stages = self.stages.clone();
+                        }
+                        "次のゲームから編集したステージで遊べます。".to_string()
+                    }
+                    Err(err) => format!("{:#}", err),
+                });
+                true
+            }
+            Msg::ExportStages => {
+                match self.stage_editor.stages.validate() {
+                    Ok(()) => {
+                        let json = self.stage_editor.stages.to_json();
+                        replay::save_file(json.as_bytes(), "application/json", "stages.json");
+                        self.stage_editor.message = None;
+                    }
+                    Err(err) => self.stage_editor.message = Some(format!("{:#}", err)),
+                }
+                true
+            }
+            // 選択されたステージ定義のファイルを読み込む
+            Msg::ImportStagesFile(file) => {
+                ctx.link()
+                    .send_future(async move { Msg::ImportStages(replay::read_file(&file).await) });
+                false
+            }
+            Msg::ImportStages(bytes) => {
+                let stages = bytes
+                    .and_then(|bytes| String::from_utf8(bytes).map_err(anyhow::Error::from))
+                    .and_then(|text| StageList::from_json(&text));
+                self.stage_editor.message = Some(match stages {
+                    Ok(stages) => {
+                        self.stage_editor = StageEditor::new(stages);
+                        "読み込みました。Applyで次のゲームから使えます。".to_string()
+                    }
+                    Err(err) => format!("ステージ定義を読み込めません: {:#}", err),
+                });
+                true
+            }
+            Msg::ResetStages => {
+                self.stage_editor = StageEditor::new(StageList::default());
+                true
+            }
+            Msg::EditSprite(edit) => {
+                self.sprite_editor.edit(edit);
+                true
+            }
+            Msg::SaveSprites => {
+                self.sprite_pack = self.sprite_editor.sprite_pack();
+                self.sprite_pack.save();
+                self.reload_images(ctx);
+                self.sprite_editor.message = Some("ゲームの画像に反映しました。".to_string());
+                true
+            }
+            Msg::ClearSprites => {
+                self.sprite_pack = SpritePack::default();
+                self.sprite_pack.save();
+                self.reset_sprite_editor();
+                self.reload_images(ctx);
+                true
+            }
+            Msg::SpritePackFetched(result) => {
+                match result {
+                    Ok(Some(text)) => {
+                        let (sprite_pack, errors) = SpritePack::parse(&text);
+                        self.site_sprite_pack = sprite_pack;
+                        self.reset_sprite_editor();
+                        self.sprite_editor.message = sprite_pack_errors(&errors);
+                        self.reload_images(ctx);
+                        true
+                    }
+                    // 画像パックが無ければ組み込みの画像のまま使う
+                    Ok(None) => false,
+                    Err(err) => {
+                        log::warn!("画像パックを取得できません: {:#}", err);
+                        false
+                    }
+                }
+            }
+            Msg::LoadSpritePackFile(file) => {
+                ctx.link().send_future(async move {
+                    Msg::LoadSpritePack(replay::read_file(&file).await)
+                });
+                false
+            }
+            Msg::LoadSpritePack(bytes) => {
+                let bytes = match bytes {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        self.sprite_editor.message =
+                            Some(format!("画像パックを読み込めません: {:#}", err));
+                        return true;
+                    }
+                };
+                let (sprite_pack, errors) = SpritePack::parse(&String::from_utf8_lossy(&bytes));
+                self.sprite_pack = sprite_pack;
+                self.sprite_pack.save();
+                self.reset_sprite_editor();
+                self.reload_images(ctx);
+                self.sprite_editor.message = Some(
+                    sprite_pack_errors(&errors)
+                        .unwrap_or_else(|| "画像パックを読み込みました。".to_string()),
+                );
+                true
+            }
+            Msg::DownloadSpritePack => {
+                let text = self.sprite_editor.sprite_pack().to_text();
+                replay::save_file(text.as_bytes(), "text/plain", sprite_pack::SPRITE_PACK_URL);
+                false
+            }
+            Msg::DropFile(file) => {
+                let name = file.name();
+                let msg = if name.ends_with(".invr") {
+                    Msg::LoadReplayFile(file)
+                } else if name.ends_with(".json") {
+                    Msg::ImportStagesFile(file)
+                } else if name.ends_with(".txt") {
+                    Msg::LoadSpritePackFile(file)
+                } else {
+                    alert(&format!(
+                        "読み込めないファイルです(.invr、.json、.txtのみ): {}",
+                        name
+                    ));
+                    return false;
+                };
+                ctx.link().send_message(msg);
                 false
             }
         }
@@ -253,210 +542,573 @@ impl Component for AnimationCanvas {
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         html! {
-            <div>
+            // リプレイ、ステージ定義、画像パックのファイルはページにドロップしても読み込める
+            <div
+                ondragover={Callback::from(|e: DragEvent| e.prevent_default())}
+                ondrop={ctx.link().batch_callback(|e: DragEvent| {
+                    e.prevent_default();
+                    e.data_transfer()
+                        .and_then(|data_transfer| data_transfer.files())
+                        .and_then(|files| files.get(0))
+                        .map(Msg::DropFile)
+                })}>
                 <div id="parent-audio-button">
                     <button id="audio-button" onclick={ctx.link().callback(|_| Msg::ResetCanvas)}>{ "Click This" }</button>
                 </div>
-            // キャンバスのサイズはここで指定
-                <canvas
-                    id="canvas"
-                    width="540" height="600"
-                    ref={self.canvas.clone()}/>
+                // 全画面表示ではキャンバスを画面の中央に置く
+                <div id={viewport::GAME_SCREEN_ID}>
+                    // キャンバスの画素数は論理的な解像度とし、表示サイズはウィンドウに合わせる
+                    <canvas
+                        id="canvas"
+                        width={viewport::LOGICAL_WIDTH.to_string()}
+                        height={viewport::LOGICAL_HEIGHT.to_string()}
+                        ref={self.canvas.clone()}/>
+                </div>
+                // スマートフォンなどタッチ操作の端末でのみ表示する
+                <div id={viewport::TOUCH_CONTROLS_ID} class="touch-controls">
+                    { self.touch_button("◀", input::Action::Left) }
+                    { self.touch_button("▶", input::Action::Right) }
+                    { self.touch_button("FIRE", input::Action::Shot) }
+                    { self.touch_button("PAUSE", input::Action::Pause) }
+                    { self.touch_button("LEVEL", input::Action::Difficulty) }
+                </div>
                 <div class="volume-buttons-list">
                     <button class="volume-button" onclick={ctx.link().callback(|_| Msg::AudioVolumeUp)}>{ "Volume Up" }</button>
                     <button class="volume-button" onclick={ctx.link().callback(|_| Msg::AudioVolumeReset)}>{ "Reset Volume" }</button>
                     <button class="volume-button" onclick={ctx.link().callback(|_| Msg::AudioVolumeDown)}>{ "Volume Down" }</button>
                 </div>
+                <div class="replay-buttons-list">
+                    <button class="replay-button" onclick={ctx.link().callback(|_| Msg::PlayReplay)}>{ "Replay Last Game" }</button>
+                    <button class="replay-button" onclick={ctx.link().callback(|_| Msg::DownloadReplay)}>{ "Download Replay" }</button>
+                    <label class="replay-button">
+                        { "Load Replay" }
+                        <input type="file" accept=".invr" hidden=true onchange={ctx.link().batch_callback(|e: Event| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            let file = input.files().and_then(|files| files.get(0));
+                            // 同じファイルを続けて選んでも読み込まれるようにする
+                            input.set_value("");
+                            file.map(Msg::LoadReplayFile)
+                        })}/>
+                    </label>
+                </div>
+                { self.key_bindings(ctx) }
+                { self.stage_editor(ctx) }
+                { self.sprite_editor(ctx) }
+                <div class="input-settings">
+                    <label>
+                        <input type="checkbox" checked={self.semi_auto}
+                            onchange={ctx.link().callback(|e: Event| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                Msg::SetSemiAuto(input.checked())
+                            })}/>
+                        { "Semi-auto Fire" }
+                    </label>
+                    <label>
+                        { "Theme " }
+                        <select onchange={ctx.link().batch_callback(|e: Event| {
+                            let select: HtmlSelectElement = e.target_unchecked_into();
+                            ThemePreset::from_name(&select.value()).map(Msg::SetTheme)
+                        })}>
+                            { for ThemePreset::ALL.into_iter().map(|theme_preset| html! {
+                                <option value={theme_preset.name()} selected={theme_preset == self.theme_preset}>
+                                    { theme_preset.label() }
+                                </option>
+                            }) }
+                        </select>
+                    </label>
+                    { self.cellophane_settings(ctx) }
+                    <label>
+                        { "Scale " }
+                        <select onchange={ctx.link().batch_callback(|e: Event| {
+                            let select: HtmlSelectElement = e.target_unchecked_into();
+                            ScaleMode::from_name(&select.value()).map(Msg::SetScaleMode)
+                        })}>
+                            { for ScaleMode::ALL.into_iter().map(|scale_mode| html! {
+                                <option value={scale_mode.name()} selected={scale_mode == self.scale_mode}>
+                                    { scale_mode.label() }
+                                </option>
+                            }) }
+                        </select>
+                    </label>
+                    <button onclick={Callback::from(|_| viewport::toggle_fullscreen())}>{ "Fullscreen" }</button>
+                    <label>
+                        { format!("Stick Deadzone: {:.2}", self.gamepads.deadzone) }
+                        <input type="range" min="0" max="0.9" step="0.05"
+                            value={self.gamepads.deadzone.to_string()}
+                            oninput={ctx.link().batch_callback(|e: InputEvent| {
+                                let input: HtmlInputElement = e.target_unchecked_into();
+                                input.value().parse().ok().map(Msg::SetDeadzone)
+                            })}/>
+                    </label>
+                </div>
             </div>
         }
     }
+
+    // ドット絵エディタのキャンバスは表示を更新するたびに描き直す
+    fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
+        if let Some(canvas) = self.sprite_canvas.cast::<HtmlCanvasElement>() {
+            let ctx: CanvasRenderingContext2d =
+                canvas.get_context("2d").unwrap().unwrap().unchecked_into();
+            self.sprite_editor.render(&ctx, &self.theme);
+        }
+    }
 }
 
 impl AnimationCanvas {
-    fn main_loop(&mut self) {
-        let canvas: HtmlCanvasElement = self.canvas.cast().unwrap();
-        let ctx: CanvasRenderingContext2d =
-            canvas.get_context("2d").unwrap().unwrap().unchecked_into();
-        match self.scene {
-            Scene::Title => {
-                // スタートボタンが押されたらゲーム開始
-                if self.input_key_down.borrow().shot {
-                    self.need_to_screen_init = true;
-                    self.new_game = true;
-                    self.scene = Scene::LaunchStage(120);
-                } else {
-                    self.title.render(&ctx);
+    // タイトル画面に表示するキーを現在の割り当てに合わせる
+    fn update_title_keys(&mut self) {
+        self.title.difficulty_key = self
+            .keyboard
+            .borrow()
+            .input_map
+            .key_label(input::Action::Difficulty);
+    }
+    // セロハンの有効・無効と、貼る範囲の設定
+    fn cellophane_settings(&self, ctx: &Context<Self>) -> Html {
+        let canvas_height = self.game.canvas_height.to_string();
+        let bands = self.cellophane.bands.iter().enumerate().map(|(i, band)| {
+            let (top, bottom) = (band.top, band.bottom);
+            let range = |value: f64, on_input: fn(f64, f64, f64) -> (f64, f64)| {
+                html! {
+                    <input type="range" min="0" max={canvas_height.clone()} step="5"
+                        value={value.to_string()}
+                        disabled={!self.cellophane.enabled}
+                        oninput={ctx.link().batch_callback(move |e: InputEvent| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            input.value().parse().ok().map(|value| {
+                                let (top, bottom) = on_input(value, top, bottom);
+                                Msg::SetCellophaneBand(i, top, bottom)
+                            })
+                        })}/>
                 }
+            };
+            html! {
+                <label>
+                    { format!("{}: {}-{}", cellophane::BAND_LABELS[i], top, bottom) }
+                    { range(top, |value, _, bottom| (value, bottom.max(value))) }
+                    { range(bottom, |value, top, _| (top.min(value), value)) }
+                </label>
             }
-            Scene::Pause => {
-                // ポーズボタンが押されたらゲーム再開
-                if self.pause.toggle_pause(self.input_key_down.borrow().pause) {
-                    self.scene = Scene::Play;
-                }
+        });
+        html! {
+            <>
+                <label>
+                    <input type="checkbox" checked={self.cellophane.enabled}
+                        onchange={ctx.link().callback(|e: Event| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            Msg::SetCellophane(input.checked())
+                        })}/>
+                    { "Cellophane Overlay" }
+                </label>
+                { for bands }
+            </>
+        }
+    }
+    // キー割り当ての設定画面
+    fn key_bindings(&self, ctx: &Context<Self>) -> Html {
+        let keyboard = self.keyboard.borrow();
+        let rows = input::Action::ALL.into_iter().map(|action| {
+            let button_text = if keyboard.rebinding == Some(action) {
+                "Press a key..."
+            } else {
+                "Change"
+            };
+            html! {
+                <tr>
+                    <td>{ action.label() }</td>
+                    <td>{ keyboard.input_map.codes(action).join(", ") }</td>
+                    <td>
+                        <button class="key-binding-button"
+                            onclick={ctx.link().callback(move |_| Msg::StartRebind(action))}>
+                            { button_text }
+                        </button>
+                    </td>
+                </tr>
             }
-            Scene::LaunchStage(cnt) => {
-                // インベーダーを全滅させた後は休憩のため長めに間をおく
-                if self.stage_number > 1 && cnt > 120 {
-                    self.scene = Scene::LaunchStage(cnt - 1);
-
-                    window()
-                        .unwrap()
-                        .request_animation_frame(self.callback.as_ref().unchecked_ref())
-                        .unwrap();
-                    return;
+        });
+        html! {
+            <details class="key-bindings">
+                <summary>{ "Key Bindings" }</summary>
+                <table>{ for rows }</table>
+                if let Some(error) = &self.rebind_error {
+                    <p class="key-binding-error">{ error }</p>
                 }
-                ctx.set_global_alpha(1.);
-                // 画像のぼやけを防ぐ
-                ctx.set_image_smoothing_enabled(false);
-
-                // 画面全体の初期化
-                if self.need_to_screen_init {
-                    ctx.set_fill_style(&JsValue::from("rgb(0,0,0)"));
-                    ctx.fill_rect(0.0, 0.0, canvas.width().into(), canvas.height().into());
-                    // プレイヤーの下に赤線を描く
-                    ctx.set_stroke_style(&JsValue::from("rgb(180,0,0)"));
-                    ctx.set_line_width(2.);
-                    ctx.begin_path();
-                    ctx.move_to(0., self.canvas_height - 40.);
-                    ctx.line_to(self.canvas_width - 0., self.canvas_height - 40.);
-                    ctx.stroke();
-                    // トーチカの描画サイズ
-                    let (torchika_width, torchika_height) = (
-                        self.torchika.as_ref().unwrap().width() as f64 * 3.,
-                        self.torchika.as_ref().unwrap().height() as f64 * 3.,
-                    );
-                    let torchika_start = self.canvas_width / 2. - 175.;
-                    // トーチカ描画
-                    for i in 0..4 {
-                        ctx.draw_image_with_image_bitmap_and_dw_and_dh(
-                            &self.torchika.as_ref().unwrap(),
-                            torchika_start + 120. * i as f64 - torchika_width / 2.,
-                            self.canvas_height - 180.,
-                            torchika_width,
-                            torchika_height,
-                        )
-                        .unwrap();
-                    }
-                    // 新しくゲーム開始
-                    if self.new_game {
-                        self.stage_number = 1;
-                        self.player.all_reset();
-                    } else {
-                        // ステージが進む
-                        self.player.stage_reset();
-                    }
-                    self.enemy_manage.reset(&ctx, self.stage_number);
-                    self.ufo.reset(&ctx);
+                <button class="key-binding-button" onclick={ctx.link().callback(|_| Msg::ResetKeyBindings)}>
+                    { "Reset to Default" }
+                </button>
+            </details>
+        }
+    }
 
-                    // 初期化は最初のみ
-                    self.need_to_screen_init = false;
-                    self.new_game = false;
+    // ステージエディタ
+    // マスをクリックして選んだ種類のインベーダーを置き、開始時の高さや射撃頻度、トーチカの位置を決める
+    fn stage_editor(&self, ctx: &Context<Self>) -> Html {
+        let editor = &self.stage_editor;
+        let stage = editor.stage();
+        let edit = |edit: StageEdit| ctx.link().callback(move |_| Msg::EditStage(edit));
+        // 数値の入力欄の値から操作を作る
+        fn on_number<T: std::str::FromStr + 'static>(
+            ctx: &Context<AnimationCanvas>,
+            edit: impl Fn(T) -> StageEdit + 'static,
+        ) -> Callback<Event> {
+            ctx.link().batch_callback(move |e: Event| {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                input
+                    .value()
+                    .parse()
+                    .ok()
+                    .map(|value| Msg::EditStage(edit(value)))
+            })
+        }
+        let stage_options = (0..editor.stages.stages.len()).map(|i| {
+            html! {
+                <option value={i.to_string()} selected={i == editor.current}>
+                    { format!("Stage {}", i + 1) }
+                </option>
+            }
+        });
+        let brushes = [
+            ("Squid", Some(EnemyType::Squid)),
+            ("Crab", Some(EnemyType::Crab)),
+            ("Octopus", Some(EnemyType::Octopus)),
+            ("Erase", None),
+        ]
+        .into_iter()
+        .map(|(label, brush)| {
+            let class = classes!(
+                "stage-editor-button",
+                (editor.brush == brush).then_some("selected")
+            );
+            html! {
+                <button {class} onclick={edit(StageEdit::Brush(brush))}>{ label }</button>
+            }
+        });
+        let grid = (0..stage.rows.len()).map(|row| {
+            let cells = (0..stage.columns).map(|column| {
+                let (label, class) = match stage.cell(row, column) {
+                    Some(EnemyType::Squid) => ("S", "squid"),
+                    Some(EnemyType::Crab) => ("C", "crab"),
+                    Some(EnemyType::Octopus) => ("O", "octopus"),
+                    None => ("", "empty"),
+                };
+                html! {
+                    <td>
+                        <button class={classes!("stage-cell", class)}
+                            onclick={edit(StageEdit::Paint(row, column))}>
+                            { label }
+                        </button>
+                    </td>
                 }
-
-                // 敵インベーダーの処理
-                // プレイヤーが操作可能になるまで敵は動くが攻撃しない
-                self.enemy_manage.set_shot_interval(0);
-                self.enemy_manage
-                    .update(&ctx, &mut self.player, &self.audio);
-                self.enemy_manage.render(&ctx);
-
-                // 一定時間経過するまで繰り返す
-                if cnt < 0 {
-                    self.scene = Scene::Play;
-                    // UFOの出現タイマーを初期化する
-                    self.ufo.reset_timer();
-
-                    log::info!("Stage{} start.", self.stage_number);
-                } else {
-                    self.scene = Scene::LaunchStage(cnt - 1);
+            });
+            html! { <tr>{ for cells }</tr> }
+        });
+        let torchika = stage.torchika.iter().enumerate().map(|(i, x)| {
+            html! {
+                <li>
+                    <input type="number" step="5"
+                        min={(-stage::MAX_TORCHIKA_OFFSET).to_string()}
+                        max={stage::MAX_TORCHIKA_OFFSET.to_string()}
+                        value={x.to_string()}
+                        onchange={on_number(ctx, move |x| StageEdit::MoveTorchika(i, x))}/>
+                    <button class="stage-editor-button" onclick={edit(StageEdit::RemoveTorchika(i))}>
+                        { "Remove" }
+                    </button>
+                </li>
+            }
+        });
+        html! {
+            <details class="stage-editor">
+                <summary>{ "Stage Editor" }</summary>
+                <div class="stage-editor-row">
+                    <select onchange={on_number(ctx, StageEdit::Select)}>{ for stage_options }</select>
+                    <button class="stage-editor-button" onclick={edit(StageEdit::AddStage)}>{ "Duplicate Stage" }</button>
+                    <button class="stage-editor-button" onclick={edit(StageEdit::RemoveStage)}>{ "Remove Stage" }</button>
+                    <label>
+                        { "Loop to: " }
+                        <input type="number" min="1" max={editor.stages.stages.len().to_string()}
+                            value={editor.stages.loop_to.to_string()}
+                            onchange={on_number(ctx, StageEdit::LoopTo)}/>
+                    </label>
+                </div>
+                <div class="stage-editor-row">{ for brushes }</div>
+                <table class="stage-grid">{ for grid }</table>
+                <div class="stage-editor-row">
+                    <button class="stage-editor-button" onclick={edit(StageEdit::AddRow)}>{ "Add Row" }</button>
+                    <button class="stage-editor-button" onclick={edit(StageEdit::RemoveRow)}>{ "Remove Row" }</button>
+                    <label>
+                        { "Columns: " }
+                        <input type="number" min="1" max={stage::MAX_COLUMNS.to_string()}
+                            value={stage.columns.to_string()}
+                            onchange={on_number(ctx, StageEdit::Columns)}/>
+                    </label>
+                </div>
+                <div class="stage-editor-row">
+                    <label>
+                        { format!("Start Height: {}", stage.start_height) }
+                        <input type="range" min="0" max={stage::MAX_START_HEIGHT.to_string()}
+                            value={stage.start_height.to_string()}
+                            onchange={on_number(ctx, StageEdit::StartHeight)}/>
+                    </label>
+                    <label>
+                        { "Fire Rate: " }
+                        <input type="number" min="0.1" max="10" step="0.1"
+                            value={stage.fire_rate.to_string()}
+                            onchange={on_number(ctx, StageEdit::FireRate)}/>
+                    </label>
+                </div>
+                <div class="stage-editor-row">
+                    { "Torchika (offset from center):" }
+                    <ul>{ for torchika }</ul>
+                    <button class="stage-editor-button" onclick={edit(StageEdit::AddTorchika)}>{ "Add Torchika" }</button>
+                </div>
+                if let Some(message) = &editor.message {
+                    <p class="stage-editor-message">{ message }</p>
                 }
+                <div class="stage-editor-row">
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::PreviewStage)}>{ "Preview" }</button>
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::ApplyStages)}>{ "Apply" }</button>
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::ExportStages)}>{ "Export" }</button>
+                    <label class="stage-editor-button">
+                        { "Import" }
+                        <input type="file" accept=".json" hidden=true onchange={ctx.link().batch_callback(|e: Event| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            let file = input.files().and_then(|files| files.get(0));
+                            input.set_value("");
+                            file.map(Msg::ImportStagesFile)
+                        })}/>
+                    </label>
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::ResetStages)}>{ "Reset to Default" }</button>
+                </div>
+            </details>
+        }
+    }
+
+    // ドット絵エディタ
+    // キャンバス左側のマスをクリック、ドラッグしてドットを切り替え、右側にゲーム中の倍率で表示する
+    fn sprite_editor(&self, ctx: &Context<Self>) -> Html {
+        let editor = &self.sprite_editor;
+        let options = dot_data::DOT_NAMES.into_iter().map(|name| {
+            html! {
+                <option value={name} selected={name == editor.name}>{ name }</option>
             }
-            Scene::Play => {
-                // ポーズボタンが押されたらゲーム一時停止
-                if self.pause.toggle_pause(self.input_key_down.borrow().pause) {
-                    self.scene = Scene::Pause;
+        });
+        // ボタンを押している間のみ、キャンバスが縮小表示されていても描画上の座標に直して渡す
+        let pointer = |edit: fn(f64, f64) -> SpriteEdit| {
+            ctx.link().batch_callback(move |e: PointerEvent| {
+                (e.buttons() & 1 == 1).then(|| {
+                    let canvas: HtmlCanvasElement = e.target_unchecked_into();
+                    let scale = canvas.width() as f64 / canvas.client_width().max(1) as f64;
+                    Msg::EditSprite(edit(
+                        e.offset_x() as f64 * scale,
+                        e.offset_y() as f64 * scale,
+                    ))
+                })
+            })
+        };
+        html! {
+            <details class="sprite-editor">
+                <summary>{ "Sprite Editor" }</summary>
+                <div class="stage-editor-row">
+                    <select onchange={ctx.link().callback(|e: Event| {
+                        let select: HtmlSelectElement = e.target_unchecked_into();
+                        Msg::EditSprite(SpriteEdit::Select(select.value()))
+                    })}>{ for options }</select>
+                </div>
+                <canvas class="sprite-canvas"
+                    width={sprite_editor::CANVAS_WIDTH.to_string()}
+                    height={sprite_editor::CANVAS_HEIGHT.to_string()}
+                    ref={self.sprite_canvas.clone()}
+                    onpointerdown={pointer(SpriteEdit::Press)}
+                    onpointermove={pointer(SpriteEdit::Drag)}
+                    onpointerup={ctx.link().callback(|_| Msg::EditSprite(SpriteEdit::Release))}
+                    onpointerleave={ctx.link().callback(|_| Msg::EditSprite(SpriteEdit::Release))}/>
+                <textarea class="sprite-text" rows="8" value={editor.text.clone()}
+                    oninput={ctx.link().callback(|e: InputEvent| {
+                        let textarea: HtmlTextAreaElement = e.target_unchecked_into();
+                        Msg::EditSprite(SpriteEdit::SetText(textarea.value()))
+                    })}/>
+                if let Some(message) = &editor.message {
+                    <p class="stage-editor-message">{ message }</p>
                 }
-                ctx.set_global_alpha(1.);
-                // 画像のぼやけを防ぐ
-                ctx.set_image_smoothing_enabled(false);
-                // プレイヤーの処理
-                self.player
-                    .update(&ctx, &self.input_key_down.borrow(), &self.audio);
-                // 敵インベーダーの処理
-                self.enemy_manage
-                    .update(&ctx, &mut self.player, &self.audio);
+                <div class="stage-editor-row">
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::EditSprite(SpriteEdit::LoadText))}>{ "Load Text" }</button>
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::EditSprite(SpriteEdit::Revert))}>{ "Revert" }</button>
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::SaveSprites)}>{ "Use in Game" }</button>
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::ClearSprites)}>{ "Reset All" }</button>
+                </div>
+                <div class="stage-editor-row">
+                    <button class="stage-editor-button" onclick={ctx.link().callback(|_| Msg::DownloadSpritePack)}>{ "Download Pack" }</button>
+                    <label class="stage-editor-button">
+                        { "Load Pack" }
+                        <input type="file" accept=".txt" hidden=true onchange={ctx.link().batch_callback(|e: Event| {
+                            let input: HtmlInputElement = e.target_unchecked_into();
+                            let file = input.files().and_then(|files| files.get(0));
+                            input.set_value("");
+                            file.map(Msg::LoadSpritePackFile)
+                        })}/>
+                    </label>
+                </div>
+            </details>
+        }
+    }
 
-                // UFOの処理
-                self.ufo.update(
-                    &ctx,
-                    self.canvas_width,
-                    &mut self.player.bullet,
-                    &self.audio,
-                );
+    // 描画に使う画像パック(取得したものを保存したもので置き換える)
+    fn sprite_pack_in_use(&self) -> SpritePack {
+        let mut sprite_pack = self.site_sprite_pack.clone();
+        sprite_pack.merge(&self.sprite_pack);
+        sprite_pack
+    }
+    // 編集中の画像を描画に使う画像に合わせる
+    fn reset_sprite_editor(&mut self) {
+        self.sprite_editor = SpriteEditor::new(&self.sprite_pack_in_use());
+    }
+    // 画像や文字を描く配色(セロハンを貼る場合は白で描く)
+    fn render_theme(&self) -> Theme {
+        if self.cellophane.enabled {
+            self.theme.monochrome()
+        } else {
+            self.theme.clone()
+        }
+    }
+    // 置き換えた画像でビットマップ画像を作り直す
+    fn reload_images(&self, ctx: &Context<Self>) {
+        let (image_data_list, image_rgb_list) =
+            load_image::image_data_collect(&self.sprite_pack_in_use(), &self.render_theme());
+        ctx.link().send_message(Msg::RetBitmapImage(
+            ImageType::ret_all_types(),
+            image_data_list,
+            image_rgb_list,
+        ));
+    }
 
-                self.player.render(&ctx);
-                self.enemy_manage.render(&ctx);
-                self.ufo.render(&ctx);
+    // 押している間だけ対応する入力を真にするタッチ操作用のボタン
+    // タッチはボタンごとに受け取るため、移動しながら射撃できる
+    fn touch_button(&self, label: &str, action: input::Action) -> Html {
+        let touch = Rc::clone(&self.input_touch);
+        let touch_start = Callback::Callback {
+            cb: Rc::new(move |e: TouchEvent| {
+                // スクロールや拡大、マウス操作としての扱いを防ぐ
+                e.prevent_default();
+                touch.borrow_mut().set(action, true);
+            }),
+            passive: Some(false),
+        };
+        let touch = Rc::clone(&self.input_touch);
+        let touch_end = Callback::from(move |_: TouchEvent| touch.borrow_mut().set(action, false));
+        let touch_cancel = touch_end.clone();
+        html! {
+            <button class="touch-button"
+                ontouchstart={touch_start}
+                ontouchend={touch_end}
+                ontouchcancel={touch_cancel}>
+                { label }
+            </button>
+        }
+    }
 
-                if let Some(enemy_pos_y) = self.enemy_manage.nadir_y() {
-                    // 敵インベーダーがプレイヤーの高さまで侵攻した場合
-                    if self.player.pos.y - self.player.height / 2. < enemy_pos_y {
-                        // プレイヤーは破壊される
-                        self.player.break_cnt = Some(self.player.revival_set_cnt);
-                        // ゲームオーバー
-                        self.scene = Scene::GameOver(140);
-                    }
-                } else {
-                    // インベーダーが全滅した場合
-                    // 画面を初期化する
-                    self.need_to_screen_init = true;
-                    self.new_game = false;
-                    // ステージは9面の次は2面に戻る
-                    self.stage_number = if self.stage_number >= 9 {
-                        2
-                    } else {
-                        self.stage_number + 1
-                    };
-                    self.scene = Scene::LaunchStage(240);
-                }
-                // プレイヤーの残機が無くなったら
-                if self.player.life <= 0 {
-                    // ゲームオーバー
-                    self.scene = Scene::GameOver(140);
+    // 記録したときと同じシード値の初期状態からリプレイを再生する
+    fn start_playback(&mut self, replay: Replay) {
+        match replay.check(
+            self.game.canvas_width,
+            self.game.canvas_height,
+            &self.stages,
+        ) {
+            Err(err) => {
+                alert(&format!("このリプレイは再生できません: {}", err));
+                return;
+            }
+            Ok(Some(warning)) => {
+                log::warn!("{}", warning);
+                let confirmed = window()
+                    .unwrap()
+                    .confirm_with_message(&format!("{}\n再生しますか？", warning))
+                    .unwrap_or(false);
+                if !confirmed {
+                    return;
                 }
             }
-            Scene::GameOver(cnt) => {
-                self.new_game = true;
-                // プレイヤーの爆発エフェクトを最後まで表示
-                if let Some(explosion_cnt) = self.player.break_cnt {
-                    self.player
-                        .update(&ctx, &self.input_key_down.borrow(), &self.audio);
-                    // 爆発エフェクト表示が終わった後のプレイヤー復活はしない
-                    if explosion_cnt > 0 {
-                        self.player.render(&ctx);
+            Ok(None) => (),
+        }
+        // 再生中のハイスコアは保存しない
+        let score_storage = MemoryStorage::new(self.game.high_scores.to_text());
+        self.game = GameState::new(
+            self.game.canvas_width,
+            self.game.canvas_height,
+            replay.seed,
+            Box::new(score_storage),
+        );
+        self.game.play_mode = PlayMode::from_u8(replay.play_mode).unwrap_or(PlayMode::Single);
+        self.game.difficulty = Difficulty::from_u8(replay.difficulty).unwrap_or(Difficulty::Normal);
+        self.game.semi_auto = replay.semi_auto;
+        self.game.stages = self.stages.clone();
+        self.playback = Some(Playback::new(replay));
+    }
+
+    fn main_loop(&mut self, now: f64) {
+        let canvas: HtmlCanvasElement = self.canvas.cast().unwrap();
+        let ctx: CanvasRenderingContext2d =
+            canvas.get_context("2d").unwrap().unwrap().unchecked_into();
+        // 画面のリフレッシュレートに関係なく、60Hzでゲームを進める
+        // キーボード、タッチ、ゲームパッドのどの操作も受け付ける
+        let key_input = self
+            .keyboard
+            .borrow()
+            .key_down
+            .frame()
+            .merge(self.input_touch.borrow().frame())
+            .merge(self.gamepads.poll());
+        for _ in 0..self.timestep.advance(now) {
+            // 再生中は記録した入力を使い、記録が終わったらキー入力に戻す
+            let input = match self.playback.as_mut().map(Playback::next_frame) {
+                Some(Some(input)) => input,
+                Some(None) => {
+                    // 再生結果が記録と異なれば知らせる
+                    if let Some(Err(err)) = self.playback.take().map(|playback| {
+                        playback.verify(self.game.player.bullet.score.sum, self.game.last_checksum)
+                    }) {
+                        log::warn!("{}", err);
+                        alert(&err);
                     }
-                } else {
-                    // プレイヤーの爆発エフェクト表示が終わったら一定時間ゲームオーバー表示
-                    ctx.set_font("80px monospace");
-                    ctx.set_fill_style(&JsValue::from("rgba(200, 10, 10)"));
-                    ctx.fill_text(
-                        "GAME OVER",
-                        self.canvas_width / 2. - 180.,
-                        self.canvas_height / 4.,
-                    )
-                    .unwrap();
-                    self.scene = Scene::GameOver(cnt - 1);
-                }
-                if cnt < 0 {
-                    // 画面クリア
-                    ctx.set_fill_style(&JsValue::from("rgb(0,0,0)"));
-                    ctx.fill_rect(0.0, 0.0, self.canvas_width, self.canvas_height);
-                    self.ufo.reset(&ctx);
-                    // タイトルに戻る
-                    self.scene = Scene::Title;
+                    // 再生を終えたら新しい状態のタイトル画面に戻る
+                    let last_replay = self.game.last_replay.take();
+                    self.game = new_game_state(self.game.canvas_width, self.game.canvas_height);
+                    self.game.last_replay = last_replay;
+                    self.game.semi_auto = self.semi_auto;
+                    self.game.stages = self.stages.clone();
+                    key_input
                 }
+                None => key_input,
+            };
+            self.game.step(&input);
+            // 再生中は記録した状態と一定フレームごとに比べる
+            if let Some(message) = self
+                .playback
+                .as_mut()
+                .and_then(|playback| playback.check(self.game.last_checksum))
+            {
+                log::warn!("{}", message);
+            }
+        }
+        // 発生した効果音を再生
+        for sound in self.game.sounds.drain(..) {
+            self.audio.play_event(sound);
+        }
+        match self.screen.as_ref().filter(|_| self.cellophane.enabled) {
+            Some(screen) => {
+                let screen_ctx: CanvasRenderingContext2d =
+                    screen.get_context("2d").unwrap().unwrap().unchecked_into();
+                let theme = self.render_theme();
+                let mut renderer = CanvasRenderer::new(&screen_ctx, &theme, &self.images);
+                render::render(&mut renderer, &self.title, &self.game);
+                self.cellophane.composite(&ctx, screen, &self.theme);
+            }
+            None => {
+                let mut renderer = CanvasRenderer::new(&ctx, &self.theme, &self.images);
+                render::render(&mut renderer, &self.title, &self.game);
             }
         }
+
         window()
             .unwrap()
             .request_animation_frame(self.callback.as_ref().unchecked_ref())
@@ -464,25 +1116,44 @@ impl AnimationCanvas {
     }
 }
 
-#[function_component(App)]
-fn app_body() -> Html {
-    html! {
-        <>
-            <AnimationCanvas />
-        </>
+// 通常のプレイ用のゲームの状態を作る
+fn new_game_state(canvas_width: f64, canvas_height: f64) -> GameState {
+    // 乱数のシード値は現在時刻から決める
+    let seed = js_sys::Date::now() as u64;
+    GameState::new(canvas_width, canvas_height, seed, Box::new(LocalStorage))
+}
+
+// ゲーム中にキーを押してもボタンが反応しないようにフォーカスを外す
+fn blur_active_element() {
+    if let Some(element) = window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.active_element())
+        .and_then(|element| element.dyn_into::<HtmlElement>().ok())
+    {
+        element.blur().unwrap();
     }
 }
 
+// 画像パックの読み込めなかった画像の一覧(すべて読み込めたらNone)
+fn sprite_pack_errors(errors: &[String]) -> Option<String> {
+    for error in errors {
+        log::warn!("画像パックの画像を読み込めません: {}", error);
+    }
+    (!errors.is_empty()).then(|| {
+        format!(
+            "次の画像は読み込めないため組み込みの画像を使います: {}",
+            errors.join("、")
+        )
+    })
+}
+
+fn alert(message: &str) {
+    log::warn!("{}", message);
+    window().unwrap().alert_with_message(message).unwrap();
+}
+
 fn main() {
     // デバッグ出力用
     wasm_logger::init(wasm_logger::Config::default());
-    yew::start_app::<App>();
-}
-
-// 指定した範囲を背景色で塗りつぶす
-fn draw_background_rect(ctx: &CanvasRenderingContext2d, x: f64, y: f64, width: f64, height: f64) {
-    ctx.set_fill_style(&JsValue::from("rgb(0,0,0)"));
-    // firefox以外のブラウザで、画像描画範囲に対し塗りつぶし範囲が僅かにずれる
-    // その対策として、塗りつぶし範囲を1pixel増やす
-    ctx.fill_rect(x - 1., y - 1., width + 2., height + 2.);
+    yew::start_app::<AnimationCanvas>();
 }
@@ -14,11 +14,7 @@ impl Vec2 {
         let top = center_pos.y - height / 2.;
         let bottom = center_pos.y + height / 2.;
 
-        if left < self.x && self.x < right && top < self.y && self.y < bottom {
-            true
-        } else {
-            false
-        }
+        left < self.x && self.x < right && top < self.y && self.y < bottom
     }
     pub fn round(&self) -> Vec2 {
         Vec2 {
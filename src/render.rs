@@ -0,0 +1,505 @@
+use crate::enemy::{BulletType, EnemyManage, EnemyType};
+use crate::game::{GameState, Scene};
+use crate::high_score::{HighScores, NameEntry};
+use crate::load_image::ImageType;
+use crate::math::Vec2;
+use crate::player::{Bullet, Player, Score};
+use crate::renderer::{Renderer, TextAlign};
+use crate::theme::Color;
+use crate::title::Title;
+use crate::torchika::Torchika;
+use crate::ufo::Ufo;
+
+// ゲームの状態から画面全体を描く
+// 前のフレームの描画は残さず、毎フレーム背景から描き直す
+pub fn render(renderer: &mut dyn Renderer, title: &Title, game: &GameState) {
+    renderer.fill_rect(
+        0.0,
+        0.0,
+        game.canvas_width,
+        game.canvas_height,
+        Color::Background,
+    );
+    render_scene(renderer, title, game);
+    // 画面上部の得点表示は常に表示する
+    // ハイスコアは保存された記録と現在の得点の高い方
+    let (score_1, score_2) = game.player_scores();
+    let hi_score = game
+        .high_scores
+        .best()
+        .max(score_1)
+        .max(score_2.unwrap_or(0));
+    render_header(
+        renderer,
+        &game.player.bullet.score,
+        score_1,
+        hi_score,
+        score_2,
+        game.canvas_width,
+    );
+}
+
+fn render_scene(renderer: &mut dyn Renderer, title: &Title, game: &GameState) {
+    match game.scene {
+        Scene::Title => {
+            title.render(renderer, game.play_mode, game.difficulty);
+            render_high_scores(renderer, &game.high_scores, game.canvas_width);
+        }
+        // 一時停止中は止まった画面を表示し続ける
+        Scene::Pause => render_play(renderer, game),
+        Scene::LaunchStage(cnt) => {
+            // インベーダー全滅後の休憩中は直前の画面を表示し続ける
+            if cnt > 120 {
+                render_play(renderer, game);
+                return;
+            }
+            render_ground_line(renderer, game.canvas_width, game.canvas_height);
+            render_torchika_list(renderer, &game.torchika_list);
+            // プレイヤーが操作可能になるまで敵のみ表示する
+            render_enemy_manage(renderer, &game.enemy_manage);
+        }
+        Scene::Play => render_play(renderer, game),
+        Scene::GameOver(_) => {
+            // 爆発エフェクト表示が終わった後のプレイヤー復活はしない
+            render_play(renderer, game);
+            if !game.player.exploding()
+                && !game.second_player.as_ref().is_some_and(Player::exploding)
+            {
+                // プレイヤーの爆発エフェクト表示が終わったら一定時間ゲームオーバー表示
+                renderer.fill_text(
+                    "GAME OVER",
+                    game.canvas_width / 2. - 180.,
+                    game.canvas_height / 4.,
+                    80.,
+                    TextAlign::Start,
+                    Color::Message,
+                );
+            }
+        }
+        Scene::NameEntry => render_name_entry(
+            renderer,
+            &game.name_entry,
+            game.play_mode.player_count(),
+            game.canvas_width,
+            game.canvas_height,
+        ),
+        Scene::PlayerChange(_) => {
+            // 次にプレイするプレイヤーを表示する
+            renderer.fill_text(
+                &format!("PLAY PLAYER<{}>", game.current_player + 1),
+                game.canvas_width / 2. - 165.,
+                game.canvas_height / 2.,
+                40.,
+                TextAlign::Start,
+                Color::Text,
+            );
+        }
+        Scene::StagePreview => {
+            render_ground_line(renderer, game.canvas_width, game.canvas_height);
+            // 赤線の下に戻り方を表示する
+            renderer.fill_text(
+                "PREVIEW: PRESS FIRE TO RETURN",
+                20.,
+                game.canvas_height - 12.,
+                20.,
+                TextAlign::Start,
+                Color::Text,
+            );
+            render_torchika_list(renderer, &game.torchika_list);
+            render_enemy_manage(renderer, &game.enemy_manage);
+        }
+    }
+}
+
+// タイトル画面の下側にハイスコア表を描く
+fn render_high_scores(renderer: &mut dyn Renderer, high_scores: &HighScores, canvas_width: f64) {
+    renderer.fill_text(
+        "HIGH SCORES",
+        canvas_width / 2. - 80.,
+        340.,
+        24.,
+        TextAlign::Start,
+        Color::Turquoise,
+    );
+    for (rank, entry) in high_scores.entries.iter().enumerate() {
+        renderer.fill_text(
+            &format!("{:>2}. {} {:>6}", rank + 1, entry.name, entry.score),
+            canvas_width / 2. - 100.,
+            366. + 22. * rank as f64,
+            22.,
+            TextAlign::Start,
+            Color::Text,
+        );
+    }
+}
+
+// ハイスコアを出した後のイニシャル入力画面を描く
+fn render_name_entry(
+    renderer: &mut dyn Renderer,
+    name_entry: &NameEntry,
+    player_count: usize,
+    canvas_width: f64,
+    canvas_height: f64,
+) {
+    renderer.fill_text(
+        "NEW HIGH SCORE",
+        canvas_width / 2. - 165.,
+        canvas_height / 4.,
+        40.,
+        TextAlign::Start,
+        Color::Message,
+    );
+    // 2人プレイではどちらのプレイヤーの入力か示す
+    let score_text = if player_count == 2 {
+        format!("PLAYER<{}> {}", name_entry.player_number, name_entry.score)
+    } else {
+        format!("SCORE {}", name_entry.score)
+    };
+    renderer.fill_text(
+        &score_text,
+        canvas_width / 2.,
+        canvas_height / 4. + 60.,
+        30.,
+        TextAlign::Center,
+        Color::Text,
+    );
+
+    // 入力中の文字は色を変えて下線を引く
+    let left = canvas_width / 2. - 75.;
+    let baseline = canvas_height / 2.;
+    for (i, letter) in name_entry.letters.iter().enumerate() {
+        let x = left + 50. * i as f64;
+        let color = if i == name_entry.cursor {
+            renderer.fill_rect(x, baseline + 10., 36., 4., Color::Turquoise);
+            Color::Turquoise
+        } else {
+            Color::Text
+        };
+        renderer.fill_text(
+            &(*letter as char).to_string(),
+            x,
+            baseline,
+            60.,
+            TextAlign::Start,
+            color,
+        );
+    }
+
+    renderer.fill_text(
+        "LEFT/RIGHT: SELECT  SHOT: ENTER",
+        canvas_width / 2. - 175.,
+        canvas_height / 2. + 80.,
+        20.,
+        TextAlign::Start,
+        Color::Text,
+    );
+}
+
+fn render_play(renderer: &mut dyn Renderer, game: &GameState) {
+    render_ground_line(renderer, game.canvas_width, game.canvas_height);
+    render_torchika_list(renderer, &game.torchika_list);
+    render_players(renderer, game);
+    render_enemy_manage(renderer, &game.enemy_manage);
+    render_ufo(renderer, &game.ufo);
+}
+
+// プレイヤーを描く
+// 2人同時プレイでは2人目を別の色で描き、残機は画面の左右に分けて表示する
+fn render_players(renderer: &mut dyn Renderer, game: &GameState) {
+    // 残機は赤線のすぐ下に表示する
+    let life_y = game.canvas_height - 40.;
+    render_player(
+        renderer,
+        &game.player,
+        &ImageType::Player,
+        Vec2::new(20., life_y),
+    );
+    if let Some(second_player) = &game.second_player {
+        render_player(
+            renderer,
+            second_player,
+            &ImageType::Player2,
+            Vec2::new(game.canvas_width / 2. + 20., life_y),
+        );
+    }
+}
+
+// プレイヤーの下に赤線を描く
+fn render_ground_line(renderer: &mut dyn Renderer, canvas_width: f64, canvas_height: f64) {
+    renderer.draw_line(
+        Vec2::new(0., canvas_height - 40.),
+        Vec2::new(canvas_width - 0., canvas_height - 40.),
+        2.,
+        Color::GroundLine,
+    );
+}
+
+// トーチカの残っている部分を描く
+// 横に続くドットはまとめて1つの矩形で塗る
+fn render_torchika_list(renderer: &mut dyn Renderer, torchika_list: &[Torchika]) {
+    for torchika in torchika_list {
+        for (y, line) in torchika.dot_map.iter().enumerate() {
+            let mut x = 0;
+            while x < line.len() {
+                if !line[x] {
+                    x += 1;
+                    continue;
+                }
+                let run = line[x..].iter().take_while(|dot| **dot).count();
+                renderer.fill_rect(
+                    torchika.pos.x + x as f64 * torchika.scale,
+                    torchika.pos.y + y as f64 * torchika.scale,
+                    run as f64 * torchika.scale,
+                    torchika.scale,
+                    Color::Red,
+                );
+                x += run;
+            }
+        }
+    }
+}
+
+// 画面上部に SCORE<1>、HI-SCORE、SCORE<2> の見出しと点数を描く
+// 表示位置と大きさはscoreに従う
+// 2人目がいない場合、SCORE<2>の点数は表示しない
+fn render_header(
+    renderer: &mut dyn Renderer,
+    score: &Score,
+    score_1: usize,
+    hi_score: usize,
+    score_2: Option<usize>,
+    canvas_width: f64,
+) {
+    let columns = [
+        ("SCORE<1>", score.pos.x, Some(score_1)),
+        ("HI-SCORE", canvas_width / 2., Some(hi_score)),
+        ("SCORE<2>", canvas_width - score.pos.x, score_2),
+    ];
+    for (label, x, value) in columns {
+        renderer.fill_text(
+            label,
+            x,
+            score.pos.y - score.height / 2. - 6.,
+            18.,
+            TextAlign::Center,
+            Color::Text,
+        );
+        if let Some(value) = value {
+            //4桁右詰めで表示
+            renderer.fill_text(
+                &format!("{:0>4}", value),
+                x,
+                score.pos.y + score.height / 2.,
+                score.height,
+                TextAlign::Center,
+                Color::Text,
+            );
+        }
+    }
+}
+
+// 左上がposの位置から残機を表示する
+fn render_remain_life(
+    renderer: &mut dyn Renderer,
+    player: &Player,
+    image_type: &ImageType,
+    pos: Vec2,
+) {
+    renderer.fill_text(
+        &format!("{}", player.life),
+        pos.x,
+        pos.y + 25.,
+        25.,
+        TextAlign::Start,
+        Color::Turquoise,
+    );
+
+    // 数字表記-1 体のプレイヤー機を表示
+    for i in 0..player.life - 1 {
+        renderer.draw_image(
+            image_type,
+            pos.x + 40. + 50. * i as f64,
+            pos.y + 5.,
+            player.width,
+            player.height,
+        );
+    }
+}
+
+fn render_player(
+    renderer: &mut dyn Renderer,
+    player: &Player,
+    image_type: &ImageType,
+    life_pos: Vec2,
+) {
+    //残機は常に表示する
+    render_remain_life(renderer, player, image_type, life_pos);
+
+    match player.break_cnt {
+        //撃破から一定時間は爆発エフェクトを表示
+        //表示画像切替時には消したままにする
+        Some(cnt) if cnt > player.revival_set_cnt - 50 && cnt % 5 != 0 => {
+            //2種類の画像を交互に表示
+            let image_explosion = if (cnt / 5) % 2 == 0 {
+                ImageType::PlayerExplosion1
+            } else {
+                ImageType::PlayerExplosion2
+            };
+            draw_image(
+                renderer,
+                &image_explosion,
+                player.pos,
+                player.width_explosion,
+                player.height_explosion,
+            );
+        }
+        Some(_) => (),
+        None => draw_image(
+            renderer,
+            image_type,
+            player.pos,
+            player.width,
+            player.height,
+        ),
+    }
+    render_player_bullet(renderer, &player.bullet);
+}
+
+fn render_player_bullet(renderer: &mut dyn Renderer, bullet: &Bullet) {
+    // プレイヤーの弾が画面上に存在する時のみ描画する
+    if bullet.live {
+        draw_image(
+            renderer,
+            &ImageType::PlayerBullet,
+            bullet.pos,
+            bullet.width,
+            bullet.height,
+        );
+    }
+    // 着弾エフェクトを表示するか
+    if bullet.land_effect_cnt.is_some() {
+        draw_image(
+            renderer,
+            &ImageType::LandPlayerBulletFront,
+            bullet.pos,
+            bullet.width_land_effect,
+            bullet.height_land_effect,
+        );
+    }
+}
+
+fn render_enemy_manage(renderer: &mut dyn Renderer, enemy_manage: &EnemyManage) {
+    // 生きている個体のみ描画する
+    for enemy in enemy_manage.enemys_list.iter().filter(|enemy| enemy.live) {
+        // 表示画像選択
+        let show_image_front = match (&enemy.enemy_type, enemy.show_image_type) {
+            (EnemyType::Octopus, true) => ImageType::OctopusOpen,
+            (EnemyType::Octopus, false) => ImageType::OctopusClose,
+            (EnemyType::Crab, true) => ImageType::CrabBanzai,
+            (EnemyType::Crab, false) => ImageType::CrabDown,
+            (EnemyType::Squid, true) => ImageType::SquidOpen,
+            (EnemyType::Squid, false) => ImageType::SquidClose,
+        };
+        draw_image(
+            renderer,
+            &show_image_front,
+            enemy.pos,
+            enemy.width,
+            enemy.height,
+        );
+    }
+
+    // 爆発エフェクト
+    let explosion = &enemy_manage.explosion;
+    if let Some(enemy_type) = &explosion.show {
+        let image_type = match enemy_type {
+            EnemyType::Octopus => ImageType::ExplosionPurple,
+            EnemyType::Crab => ImageType::ExplosionTurquoise,
+            EnemyType::Squid => ImageType::ExpolsionGreen,
+        };
+        draw_image(
+            renderer,
+            &image_type,
+            explosion.pos,
+            explosion.width,
+            explosion.height,
+        );
+    }
+
+    //弾
+    for bullet in enemy_manage.bullets.iter() {
+        if bullet.live {
+            let image_type = match bullet.bullet_type {
+                BulletType::Plunger => ImageType::EnemyBulletPlunger,
+                BulletType::Squiggly => ImageType::EnemyBulletSquiggly,
+                BulletType::Rolling => ImageType::EnemyBulletRolling,
+            };
+            draw_image(
+                renderer,
+                &image_type,
+                bullet.pos,
+                bullet.width,
+                bullet.height,
+            );
+        }
+        let explosion = &bullet.explosion;
+        //一定時間は表示
+        if explosion.effect_cnt.is_some() {
+            draw_image(
+                renderer,
+                &ImageType::EnemyBulletExplosionFront,
+                explosion.pos,
+                explosion.width,
+                explosion.height,
+            );
+        }
+    }
+}
+
+fn render_ufo(renderer: &mut dyn Renderer, ufo: &Ufo) {
+    let explosion = &ufo.explosion;
+    if explosion.live {
+        if explosion.count >= 100 {
+            // エフェクト表示
+            draw_image(
+                renderer,
+                &ImageType::UfoExplosion,
+                explosion.pos,
+                explosion.width,
+                explosion.height,
+            );
+        } else if explosion.count > 0 {
+            // 一定時間経過したら獲得得点表示
+            renderer.fill_text(
+                &format!("{}", explosion.got_score),
+                explosion.pos.x - 14.,
+                explosion.pos.y + 8.,
+                22.,
+                TextAlign::Start,
+                Color::Purple,
+            );
+        }
+    }
+
+    // 一定時間経過して、かつ画面上に出現している場合
+    if ufo.appeared() {
+        draw_image(renderer, &ImageType::Ufo, ufo.pos, ufo.width, ufo.height);
+    }
+}
+
+// 中心位置posに指定した描画サイズで画像を描く
+fn draw_image(
+    renderer: &mut dyn Renderer,
+    image_type: &ImageType,
+    pos: Vec2,
+    width: f64,
+    height: f64,
+) {
+    renderer.draw_image(
+        image_type,
+        pos.x - width / 2.,
+        pos.y - height / 2.,
+        width,
+        height,
+    );
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use web_sys::{CanvasRenderingContext2d, ImageBitmap};
+
+use crate::load_image::ImageType;
+use crate::math::Vec2;
+use crate::theme::{Color, Theme};
+
+// 文字列を揃える位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    Start,  // 左端をxに揃える
+    Center, // 中央をxに揃える
+}
+
+// ゲームの描画に使う操作
+// ブラウザではCanvas2Dに、テストではメモリ上の画素(framebuffer.rs)に描く
+pub trait Renderer {
+    // 左上が(x, y)の範囲を塗りつぶす
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color);
+    // fromからtoまで太さline_widthの線を引く
+    fn draw_line(&mut self, from: Vec2, to: Vec2, line_width: f64, color: Color);
+    // 大きさsize [pixel]の等幅の文字列を、ベースラインをyに揃えて描く
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, size: f64, align: TextAlign, color: Color);
+    // 左上が(x, y)の範囲に拡大した画像を描く
+    fn draw_image(&mut self, image_type: &ImageType, x: f64, y: f64, width: f64, height: f64);
+}
+
+// ブラウザのキャンバスに描く
+pub struct CanvasRenderer<'a> {
+    ctx: &'a CanvasRenderingContext2d,
+    theme: &'a Theme,
+    images: &'a HashMap<ImageType, ImageBitmap>,
+}
+
+impl<'a> CanvasRenderer<'a> {
+    pub fn new(
+        ctx: &'a CanvasRenderingContext2d,
+        theme: &'a Theme,
+        images: &'a HashMap<ImageType, ImageBitmap>,
+    ) -> Self {
+        ctx.set_global_alpha(1.);
+        // 画像のぼやけを防ぐ
+        ctx.set_image_smoothing_enabled(false);
+        CanvasRenderer { ctx, theme, images }
+    }
+}
+
+impl Renderer for CanvasRenderer<'_> {
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64, color: Color) {
+        self.ctx.set_fill_style(&self.theme.style(color));
+        self.ctx.fill_rect(x, y, width, height);
+    }
+    fn draw_line(&mut self, from: Vec2, to: Vec2, line_width: f64, color: Color) {
+        self.ctx.set_stroke_style(&self.theme.style(color));
+        self.ctx.set_line_width(line_width);
+        self.ctx.begin_path();
+        self.ctx.move_to(from.x, from.y);
+        self.ctx.line_to(to.x, to.y);
+        self.ctx.stroke();
+    }
+    fn fill_text(&mut self, text: &str, x: f64, y: f64, size: f64, align: TextAlign, color: Color) {
+        self.ctx.set_font(&format!("{}px monospace", size));
+        self.ctx.set_fill_style(&self.theme.style(color));
+        self.ctx.set_text_align(match align {
+            TextAlign::Start => "start",
+            TextAlign::Center => "center",
+        });
+        self.ctx.fill_text(text, x, y).unwrap();
+        self.ctx.set_text_align("start");
+    }
+    // ビットマップ画像をまだ作っている途中なら描かない
+    fn draw_image(&mut self, image_type: &ImageType, x: f64, y: f64, width: f64, height: f64) {
+        let Some(image) = self.images.get(image_type) else {
+            return;
+        };
+        self.ctx
+            .draw_image_with_image_bitmap_and_dw_and_dh(image, x, y, width, height)
+            .unwrap();
+    }
+}
@@ -0,0 +1,432 @@
+use crate::difficulty::Difficulty;
+use crate::game::{InputFrame, PlayMode};
+use crate::stage::StageList;
+use anyhow::{anyhow, bail, ensure};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Blob, BlobPropertyBag, File, HtmlAnchorElement, Url};
+
+// リプレイファイルの形式(数値はすべてリトルエンディアン)
+//
+// ヘッダー
+//   4 byte  マジックナンバー "INVR"
+//   2 byte  ファイル形式のバージョン(FORMAT_VERSION)
+//   2 byte  ゲームの動きのバージョン(SIMULATION_VERSION)
+//   8 byte  乱数のシード値
+//   1 byte  難易度(0: NORMAL、1: EASY、2: HARD、3: ARCADE)
+//   2 byte  キャンバスの幅 [pixel]
+//   2 byte  キャンバスの高さ [pixel]
+//   1 byte  開始ステージ
+//   1 byte  遊び方(1: 1人プレイ、2: 2人交互プレイ、3: 2人同時プレイ)
+//   1 byte  射撃の方式(0: 押し続けて連射、1: セミオート)
+//   8 byte  ステージの定義のハッシュ値
+// 本体
+//   4 byte  ランの数m
+//   m * 3 byte  ラン(1 byte 入力のビット列 + 2 byte 連続するフレーム数)
+//               入力は2人目の操作と難易度の切り替えを含む8bit
+//   2 byte  チェックポイントの間隔i [フレーム]
+//   4 byte  チェックポイントの数k
+//   k * 8 byte  iフレームごとのゲーム状態のハッシュ値
+// トレーラー
+//   4 byte  最終得点
+//   8 byte  終了時のゲーム状態のハッシュ値
+pub const FORMAT_VERSION: u16 = 1;
+// 状態のハッシュ値を記録する間隔 [フレーム]
+const CHECKPOINT_INTERVAL: u16 = 60;
+const MAGIC: &[u8; 4] = b"INVR";
+// ゲームの動きのバージョン
+// 同じ入力でも展開が変わる変更(当たり判定、速度、乱数の使い方など)をしたら1つ上げる
+const SIMULATION_VERSION: u16 = 1;
+
+// 1ゲーム分の記録(乱数のシード値と、ゲーム開始から毎フレームの入力)
+// 同じシード値で初期化したゲームに同じ入力を与えると同じ展開が再現される
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Replay {
+    pub simulation_version: u16,
+    pub seed: u64,
+    pub difficulty: u8,
+    pub canvas_width: u16,
+    pub canvas_height: u16,
+    pub start_stage: u8,
+    pub play_mode: u8,
+    pub semi_auto: bool,
+    pub stage_checksum: u64, // 記録したときのステージの定義のハッシュ値
+    pub frames: Vec<u8>,     // 1フレームの入力を1バイトにまとめたもの
+    pub checkpoint_interval: u16,
+    pub checkpoints: Vec<u64>, // checkpoint_intervalフレームごとのゲーム状態のハッシュ値
+    pub final_score: usize,
+    pub checksum: u64, // 終了時のゲーム状態のハッシュ値
+}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        canvas_width: f64,
+        canvas_height: f64,
+        play_mode: PlayMode,
+        difficulty: Difficulty,
+        semi_auto: bool,
+        stage_checksum: u64,
+    ) -> Self {
+        Replay {
+            simulation_version: SIMULATION_VERSION,
+            seed,
+            difficulty: difficulty.to_u8(),
+            canvas_width: canvas_width as u16,
+            canvas_height: canvas_height as u16,
+            start_stage: 1,
+            play_mode: play_mode.to_u8(),
+            semi_auto,
+            stage_checksum,
+            frames: Vec::new(),
+            checkpoint_interval: CHECKPOINT_INTERVAL,
+            checkpoints: Vec::new(),
+            final_score: 0,
+            checksum: 0,
+        }
+    }
+    // 1フレーム分の入力と、そのフレームを進めた後の状態のハッシュ値を記録する
+    pub fn record(&mut self, input: &InputFrame, checksum: u64) {
+        self.frames.push(input.to_bits());
+        if self
+            .frames
+            .len()
+            .is_multiple_of(self.checkpoint_interval as usize)
+        {
+            self.checkpoints.push(checksum);
+        }
+    }
+    // ゲーム終了時の結果を記録する
+    pub fn finish(&mut self, final_score: usize, checksum: u64) {
+        self.final_score = final_score;
+        self.checksum = checksum;
+    }
+
+    // このゲームで再生できるか調べる
+    // 再生できなければErr、再生はできるが結果が変わる恐れがあればSome(警告文)を返す
+    pub fn check(
+        &self,
+        canvas_width: f64,
+        canvas_height: f64,
+        stages: &StageList,
+    ) -> anyhow::Result<Option<String>> {
+        ensure!(
+            self.canvas_width == canvas_width as u16 && self.canvas_height == canvas_height as u16,
+            "画面サイズが異なります(リプレイ: {}x{}、現在: {}x{})",
+            self.canvas_width,
+            self.canvas_height,
+            canvas_width,
+            canvas_height
+        );
+        ensure!(
+            Difficulty::from_u8(self.difficulty).is_some(),
+            "対応していない難易度です: {}",
+            self.difficulty
+        );
+        ensure!(
+            PlayMode::from_u8(self.play_mode).is_some(),
+            "対応していない遊び方です: {}",
+            self.play_mode
+        );
+        ensure!(
+            self.start_stage == 1,
+            "対応していない開始ステージです: {}",
+            self.start_stage
+        );
+        ensure!(
+            self.stage_checksum == stages.checksum(),
+            "ステージの定義が異なります(記録したときと同じステージの定義を適用してください)"
+        );
+        if self.simulation_version != SIMULATION_VERSION {
+            return Ok(Some(format!(
+                "ゲームのバージョンが異なるため、記録どおりに再生されない可能性があります(リプレイ: {}、現在: {})",
+                self.simulation_version, SIMULATION_VERSION
+            )));
+        }
+        Ok(None)
+    }
+
+    // ファイルに保存する形式に変換
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.simulation_version.to_le_bytes());
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.push(self.difficulty);
+        bytes.extend_from_slice(&self.canvas_width.to_le_bytes());
+        bytes.extend_from_slice(&self.canvas_height.to_le_bytes());
+        bytes.push(self.start_stage);
+        bytes.push(self.play_mode);
+        bytes.push(self.semi_auto as u8);
+        bytes.extend_from_slice(&self.stage_checksum.to_le_bytes());
+
+        // 同じ入力が続く部分をまとめる
+        let mut runs: Vec<(u8, u16)> = Vec::new();
+        for bits in &self.frames {
+            match runs.last_mut() {
+                Some((run_bits, len)) if run_bits == bits && *len < u16::MAX => *len += 1,
+                _ => runs.push((*bits, 1)),
+            }
+        }
+        bytes.extend_from_slice(&(runs.len() as u32).to_le_bytes());
+        for (bits, len) in runs {
+            bytes.push(bits);
+            bytes.extend_from_slice(&len.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.checkpoint_interval.to_le_bytes());
+        bytes.extend_from_slice(&(self.checkpoints.len() as u32).to_le_bytes());
+        for checkpoint in &self.checkpoints {
+            bytes.extend_from_slice(&checkpoint.to_le_bytes());
+        }
+
+        bytes.extend_from_slice(&(self.final_score as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.checksum.to_le_bytes());
+        bytes
+    }
+
+    // ファイルの内容から復元
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = Reader { bytes, cursor: 0 };
+        ensure!(reader.take(4)? == MAGIC, "リプレイファイルではありません");
+        let format_version = reader.u16()?;
+        if format_version != FORMAT_VERSION {
+            bail!(
+                "対応していないリプレイ形式のバージョンです: {} (対応: {})",
+                format_version,
+                FORMAT_VERSION
+            );
+        }
+        let simulation_version = reader.u16()?;
+        let seed = reader.u64()?;
+        let difficulty = reader.u8()?;
+        let canvas_width = reader.u16()?;
+        let canvas_height = reader.u16()?;
+        let start_stage = reader.u8()?;
+        let play_mode = reader.u8()?;
+        let semi_auto = match reader.u8()? {
+            0 => false,
+            1 => true,
+            value => bail!("射撃の方式が不正です: {}", value),
+        };
+        let stage_checksum = reader.u64()?;
+
+        let run_count = reader.u32()?;
+        let mut frames = Vec::new();
+        for _ in 0..run_count {
+            let bits = reader.u8()?;
+            let len = reader.u16()?;
+            frames.extend(std::iter::repeat_n(bits, len as usize));
+        }
+        let checkpoint_interval = reader.u16()?;
+        ensure!(checkpoint_interval > 0, "チェックポイントの間隔が不正です");
+        let checkpoint_count = reader.u32()?;
+        let mut checkpoints = Vec::new();
+        for _ in 0..checkpoint_count {
+            checkpoints.push(reader.u64()?);
+        }
+
+        let final_score = reader.u32()? as usize;
+        let checksum = reader.u64()?;
+        ensure!(
+            reader.cursor == bytes.len(),
+            "リプレイファイルの末尾に余分なデータがあります"
+        );
+        Ok(Replay {
+            simulation_version,
+            seed,
+            difficulty,
+            canvas_width,
+            canvas_height,
+            start_stage,
+            play_mode,
+            semi_auto,
+            stage_checksum,
+            frames,
+            checkpoint_interval,
+            checkpoints,
+            final_score,
+            checksum,
+        })
+    }
+}
+
+// バイト列を先頭から順に読み出す
+struct Reader<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let end = self.cursor + len;
+        let slice = self
+            .bytes
+            .get(self.cursor..end)
+            .ok_or_else(|| anyhow!("リプレイファイルが途中で終わっています"))?;
+        self.cursor = end;
+        Ok(slice)
+    }
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+// 記録した入力を1フレームずつ取り出す
+pub struct Playback {
+    replay: Replay,
+    cursor: usize,                 // 次に取り出すフレーム番号
+    diverged_frame: Option<usize>, // 最初に記録と状態が食い違ったチェックポイントのフレーム番号
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Playback {
+            replay,
+            cursor: 0,
+            diverged_frame: None,
+        }
+    }
+    // 次のフレームの入力を返す(記録が終わっていればNone)
+    pub fn next_frame(&mut self) -> Option<InputFrame> {
+        let bits = *self.replay.frames.get(self.cursor)?;
+        self.cursor += 1;
+        Some(InputFrame::from_bits(bits))
+    }
+    // 直前に取り出したフレームを進めた後の状態を記録と比べる
+    // 初めて食い違いが見つかった時のみ、分岐した範囲を示すメッセージを返す
+    pub fn check(&mut self, checksum: u64) -> Option<String> {
+        let interval = self.replay.checkpoint_interval as usize;
+        if self.diverged_frame.is_some() || !self.cursor.is_multiple_of(interval) {
+            return None;
+        }
+        let expected = *self.replay.checkpoints.get(self.cursor / interval - 1)?;
+        if expected == checksum {
+            return None;
+        }
+        self.diverged_frame = Some(self.cursor);
+        Some(format!(
+            "フレーム{}で状態が記録と一致しません(フレーム{}からフレーム{}の間で分岐しました)",
+            self.cursor,
+            self.cursor - interval + 1,
+            self.cursor
+        ))
+    }
+    // 再生し終えたゲームの結果が記録と一致しているか調べる
+    pub fn verify(&self, final_score: usize, checksum: u64) -> Result<(), String> {
+        if self.replay.final_score != final_score || self.replay.checksum != checksum {
+            let diverged = match self.diverged_frame {
+                Some(frame) => format!("、最初の食い違いはフレーム{}", frame),
+                None => String::new(),
+            };
+            return Err(format!(
+                "再生結果が記録と一致しません(得点 記録: {} 再生: {}、ハッシュ値 記録: {:016x} 再生: {:016x}{})",
+                self.replay.final_score, final_score, self.replay.checksum, checksum, diverged
+            ));
+        }
+        Ok(())
+    }
+}
+
+// リプレイをファイルとしてダウンロードさせる
+pub fn download(replay: &Replay) {
+    save_file(
+        &replay.to_bytes(),
+        "application/octet-stream",
+        &format!("invader-{:016x}.invr", replay.seed),
+    );
+}
+
+// dataをファイル名file_nameでダウンロードさせる
+pub fn save_file(data: &[u8], mime_type: &str, file_name: &str) {
+    let bytes = js_sys::Uint8Array::from(data);
+    let blob = Blob::new_with_u8_array_sequence_and_options(
+        &js_sys::Array::of1(&bytes),
+        BlobPropertyBag::new().type_(mime_type),
+    )
+    .unwrap();
+    let url = Url::create_object_url_with_blob(&blob).unwrap();
+
+    let document = window().unwrap().document().unwrap();
+    let anchor: HtmlAnchorElement = document.create_element("a").unwrap().unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    Url::revoke_object_url(&url).unwrap();
+}
+
+// 選択されたファイルの内容を読み込む
+pub async fn read_file(file: &File) -> anyhow::Result<Vec<u8>> {
+    let buffer = JsFuture::from(file.array_buffer())
+        .await
+        .map_err(|err| anyhow!("{}の読み込みに失敗しました: {:?}", file.name(), err))?;
+    Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_replay() -> Replay {
+        let mut replay = Replay::new(
+            0x5EED,
+            540.,
+            600.,
+            PlayMode::CoOp,
+            Difficulty::Hard,
+            true,
+            StageList::default().checksum(),
+        );
+        for frame in 0..200u64 {
+            let input = InputFrame::from_bits((frame / 7 % 256) as u8);
+            replay.record(&input, frame.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        }
+        replay.finish(1230, 0xDEAD_BEEF);
+        replay
+    }
+
+    #[test]
+    fn round_trip() {
+        let replay = sample_replay();
+        assert_eq!(replay.checkpoints.len(), 3);
+        assert_eq!(Replay::from_bytes(&replay.to_bytes()).unwrap(), replay);
+    }
+
+    #[test]
+    fn rejects_truncated() {
+        let bytes = sample_replay().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(Replay::from_bytes(&bytes[..len]).is_err(), "{}バイト", len);
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_data() {
+        let mut bytes = sample_replay().to_bytes();
+        bytes.push(0);
+        assert!(Replay::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = sample_replay().to_bytes();
+        bytes[0] = b'X';
+        assert!(Replay::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_other_format_version() {
+        let mut bytes = sample_replay().to_bytes();
+        bytes[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(Replay::from_bytes(&bytes).is_err());
+    }
+}
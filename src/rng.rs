@@ -0,0 +1,37 @@
+// シード値から再現可能な疑似乱数生成器(xorshift64*)
+// ゲーム内の乱数はすべてここから取り出し、シミュレーション以外では進めない
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // 状態が0だと常に0を返すため、シード値をかき混ぜてから使う(splitmix64)
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Rng {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+    // 現在の内部状態(状態の比較用)
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    // 0以上n未満の整数を返す
+    pub fn gen_range(&mut self, n: usize) -> usize {
+        (((self.next_u64() >> 32) * n as u64) >> 32) as usize
+    }
+    // 確率1/nで真を返す
+    pub fn one_in(&mut self, n: usize) -> bool {
+        self.gen_range(n) == 0
+    }
+}
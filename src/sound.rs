@@ -1,3 +1,4 @@
+use crate::game::SoundEvent;
 use anyhow::anyhow;
 use js_sys::ArrayBuffer;
 use wasm_bindgen::{JsCast, JsValue};
@@ -38,6 +39,7 @@ pub struct Audio {
     pub player_explosion: Option<Sound>,
     pub ufo_flying: Option<Sound>,
     pub ufo_explosion: Option<Sound>,
+    ufo_flying_node: Option<AudioBufferSourceNode>, // ループ再生中のUFO飛行音
 }
 
 impl Audio {
@@ -50,6 +52,7 @@ impl Audio {
             player_explosion: None,
             ufo_flying: None,
             ufo_explosion: None,
+            ufo_flying_node: None,
         }
     }
     // ファイル名から音声データを取得
@@ -71,6 +74,37 @@ impl Audio {
             volume: default_volume,
         })
     }
+    // シミュレーションから要求された効果音を再生
+    pub fn play_event(&mut self, event: SoundEvent) {
+        match event {
+            SoundEvent::PlayerShot => self.play_option_sound(&self.player_shot),
+            SoundEvent::PlayerExplosion => self.play_option_sound(&self.player_explosion),
+            SoundEvent::InvaderExplosion => self.play_option_sound(&self.invader_explosion),
+            SoundEvent::UfoExplosion => self.play_option_sound(&self.ufo_explosion),
+            SoundEvent::InvaderMove(index) => {
+                // サウンドが保存されていれば順番にループ
+                if !self.invader_move.is_empty() {
+                    self.play_once_sound(&self.invader_move[index % self.invader_move.len()]);
+                }
+            }
+            SoundEvent::UfoFlyingStart => {
+                if let Some(sound) = &self.ufo_flying {
+                    self.ufo_flying_node = Some(self.play_looping_sound(sound));
+                }
+            }
+            SoundEvent::UfoFlyingStop => {
+                if let Some(sound_node) = self.ufo_flying_node.take() {
+                    sound_node.stop().unwrap();
+                }
+            }
+        }
+    }
+    // 読み込み済みであれば一度だけ再生
+    fn play_option_sound(&self, sound: &Option<Sound>) {
+        if let Some(sound) = sound {
+            self.play_once_sound(sound);
+        }
+    }
     //サウンドを一度だけ再生
     pub fn play_once_sound(&self, sound: &Sound) {
         self.play_sound(&sound.buffer, sound.volume, false);
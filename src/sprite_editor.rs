@@ -0,0 +1,227 @@
+use anyhow::ensure;
+use wasm_bindgen::JsValue;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::dot_data::{self, DotShape};
+use crate::load_image::ImageType;
+use crate::sprite_pack::SpritePack;
+use crate::theme::{Color, Theme};
+
+// 編集用のマス1つの大きさ [pixel]
+const CELL_SIZE: f64 = 14.;
+// 表示倍率どおりのプレビューを描く位置のx座標 [pixel]
+const PREVIEW_X: f64 = 320.;
+// 編集用キャンバスのサイズ [pixel]
+pub const CANVAS_WIDTH: u32 = 540;
+pub const CANVAS_HEIGHT: u32 = 240;
+
+// アニメーションで交互に表示するもう一方の画像の名前
+pub fn partner(name: &str) -> Option<&'static str> {
+    match name {
+        "octopus_open" => Some("octopus_close"),
+        "octopus_close" => Some("octopus_open"),
+        "crab_banzai" => Some("crab_down"),
+        "crab_down" => Some("crab_banzai"),
+        "squid_open" => Some("squid_close"),
+        "squid_close" => Some("squid_open"),
+        "player_explosion_1" => Some("player_explosion_2"),
+        "player_explosion_2" => Some("player_explosion_1"),
+        _ => None,
+    }
+}
+
+// ドット絵エディタでの1回分の操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpriteEdit {
+    Select(String),  // 編集する画像を選ぶ
+    Press(f64, f64), // 編集用キャンバス上の位置を押す
+    Drag(f64, f64),  // 押したまま動かす
+    Release,         // 離す
+    SetText(String), // 読み込み用の文字列を書き換える
+    LoadText,        // 文字列のドットマップを編集中の画像に読み込む
+    Revert,          // 編集中の画像を組み込みのドットマップに戻す
+}
+
+// ドット絵の編集状態
+pub struct SpriteEditor {
+    pub name: &'static str,                // 編集中の画像の名前
+    shapes: Vec<(&'static str, DotShape)>, // すべての画像の編集中のドットマップ
+    drag_value: Option<bool>,              // ドラッグ中に塗る値
+    pub text: String,                      // 書き出し、読み込み用の"# _"形式の文字列
+    pub message: Option<String>,           // 読み込みや保存の結果
+}
+
+impl SpriteEditor {
+    pub fn new(sprite_pack: &SpritePack) -> Self {
+        let shapes = dot_data::DOT_NAMES
+            .into_iter()
+            .map(|name| (name, sprite_pack.shape(name)))
+            .collect();
+        let mut sprite_editor = SpriteEditor {
+            name: dot_data::DOT_NAMES[0],
+            shapes,
+            drag_value: None,
+            text: String::new(),
+            message: None,
+        };
+        sprite_editor.select(dot_data::DOT_NAMES[0]);
+        sprite_editor
+    }
+    pub fn shape(&self, name: &str) -> &DotShape {
+        &self
+            .shapes
+            .iter()
+            .find(|(other, _)| *other == name)
+            .expect("不明な画像の名前です。")
+            .1
+    }
+    fn shape_mut(&mut self) -> &mut DotShape {
+        let name = self.name;
+        &mut self
+            .shapes
+            .iter_mut()
+            .find(|(other, _)| *other == name)
+            .expect("不明な画像の名前です。")
+            .1
+    }
+    pub fn edit(&mut self, edit: SpriteEdit) {
+        match edit {
+            SpriteEdit::Select(name) => self.select(&name),
+            SpriteEdit::Press(offset_x, offset_y) => self.press(offset_x, offset_y),
+            SpriteEdit::Drag(offset_x, offset_y) => self.drag(offset_x, offset_y),
+            SpriteEdit::Release => self.drag_value = None,
+            SpriteEdit::SetText(text) => self.text = text,
+            SpriteEdit::LoadText => {
+                self.message = self
+                    .load_text()
+                    .err()
+                    .map(|err| format!("読み込めません: {:#}", err));
+            }
+            SpriteEdit::Revert => self.revert(),
+        }
+    }
+    // 編集する画像を選ぶ
+    fn select(&mut self, name: &str) {
+        if let Some(known) = dot_data::DOT_NAMES.into_iter().find(|known| *known == name) {
+            self.name = known;
+            self.text = self.shape(known).to_source();
+            self.message = None;
+        }
+    }
+    // キャンバス上の位置(offset_x, offset_y)を押したらそのドットを反転し、
+    // ドラッグ中は同じ値で塗る
+    fn press(&mut self, offset_x: f64, offset_y: f64) {
+        if let Some((x, y)) = self.cell_at(offset_x, offset_y) {
+            let dot = &mut self.shape_mut().dot_map[y][x];
+            *dot = !*dot;
+            self.drag_value = Some(*dot);
+            self.text = self.shape(self.name).to_source();
+        }
+    }
+    fn drag(&mut self, offset_x: f64, offset_y: f64) {
+        let (Some(value), Some((x, y))) = (self.drag_value, self.cell_at(offset_x, offset_y))
+        else {
+            return;
+        };
+        if self.shape(self.name).dot_map[y][x] != value {
+            self.shape_mut().dot_map[y][x] = value;
+            self.text = self.shape(self.name).to_source();
+        }
+    }
+    fn cell_at(&self, offset_x: f64, offset_y: f64) -> Option<(usize, usize)> {
+        let shape = self.shape(self.name);
+        let x = (offset_x / CELL_SIZE).floor();
+        let y = (offset_y / CELL_SIZE).floor();
+        (0. <= x && x < shape.width as f64 && 0. <= y && y < shape.height as f64)
+            .then_some((x as usize, y as usize))
+    }
+    // textの"# _"形式のドットマップを編集中の画像に読み込む
+    fn load_text(&mut self) -> anyhow::Result<()> {
+        let shape = DotShape::from_source(&self.text)?;
+        let current = self.shape(self.name);
+        ensure!(
+            (shape.width, shape.height) == (current.width, current.height),
+            "{}のサイズは{}x{}にしてください: {}x{}",
+            self.name,
+            current.width,
+            current.height,
+            shape.width,
+            shape.height
+        );
+        *self.shape_mut() = shape;
+        Ok(())
+    }
+    // 編集中の画像を組み込みのドットマップに戻す
+    fn revert(&mut self) {
+        *self.shape_mut() = dot_data::ret_dot_data(self.name);
+        self.text = self.shape(self.name).to_source();
+    }
+    // 組み込みと異なる画像をまとめる
+    pub fn sprite_pack(&self) -> SpritePack {
+        let mut sprite_pack = SpritePack::default();
+        for (name, shape) in &self.shapes {
+            // サイズは変えられないため失敗しない
+            sprite_pack.set(name, shape.clone()).unwrap();
+        }
+        sprite_pack
+    }
+
+    // 左に編集用の拡大したマス目、右にゲーム中の倍率と色で両方のアニメーション画像を描く
+    pub fn render(&self, ctx: &CanvasRenderingContext2d, theme: &Theme) {
+        ctx.set_fill_style(&theme.style(Color::Background));
+        ctx.fill_rect(0., 0., CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+        // ゲーム中と同じ倍率と色で描く(同じ形を複数の色で描く場合は色ごとに並べる)
+        let scale = dot_data::display_scale(self.name);
+        let colors: Vec<Color> = ImageType::ret_all_types()
+            .into_iter()
+            .map(|image_type| image_type.source())
+            .filter(|(name, _)| *name == self.name)
+            .map(|(_, color)| color)
+            .collect();
+        let color = theme.style(colors[0]);
+        let shape = self.shape(self.name);
+        for (y, line) in shape.dot_map.iter().enumerate() {
+            for (x, dot) in line.iter().enumerate() {
+                let style = if *dot {
+                    color.clone()
+                } else {
+                    JsValue::from("rgb(30,30,30)")
+                };
+                ctx.set_fill_style(&style);
+                ctx.fill_rect(
+                    x as f64 * CELL_SIZE + 1.,
+                    y as f64 * CELL_SIZE + 1.,
+                    CELL_SIZE - 2.,
+                    CELL_SIZE - 2.,
+                );
+            }
+        }
+        // 表示倍率どおりのプレビュー
+        let frames = std::iter::once(self.name).chain(partner(self.name));
+        let mut y = 10.;
+        for name in frames {
+            let shape = self.shape(name);
+            ctx.set_fill_style(&theme.style(Color::Text));
+            ctx.set_font("12px monospace");
+            ctx.fill_text(name, PREVIEW_X, y + 10.).unwrap();
+            y += 18.;
+            for (i, color) in colors.iter().enumerate() {
+                let x = PREVIEW_X + i as f64 * (shape.width as f64 * scale + 8.);
+                ctx.set_fill_style(&theme.style(*color));
+                for (dot_y, line) in shape.dot_map.iter().enumerate() {
+                    for (dot_x, dot) in line.iter().enumerate() {
+                        if *dot {
+                            ctx.fill_rect(
+                                x + dot_x as f64 * scale,
+                                y + dot_y as f64 * scale,
+                                scale,
+                                scale,
+                            );
+                        }
+                    }
+                }
+            }
+            y += shape.height as f64 * scale + 16.;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::{anyhow, ensure, Context};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{window, Response};
+
+use crate::dot_data::{self, DotShape};
+
+// ドット絵エディタで保存した画像をlocalStorageに保存するときのキー
+const LOCAL_STORAGE_KEY: &str = "invader-yew-sprite-overrides";
+// 起動時に読み込む画像パックのURL(無ければ組み込みの画像のみ使う)
+pub const SPRITE_PACK_URL: &str = "sprites.txt";
+
+// 組み込みのドットマップの代わりに描画に使う、名前付きの画像の集まり
+// 画像ごとに"[名前]"の行に続けてdot_data.rsと同じ"# _"形式のドットマップを書く
+// 当たり判定には影響しないように、組み込みと同じサイズのものだけ使える
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpritePack {
+    pub shapes: Vec<(String, DotShape)>,
+}
+
+impl SpritePack {
+    // 保存された画像を読み込む(読み込めない画像は組み込みの画像を使う)
+    pub fn load() -> Self {
+        let storage = window().and_then(|window| window.local_storage().ok().flatten());
+        let text = storage
+            .and_then(|storage| storage.get_item(LOCAL_STORAGE_KEY).ok().flatten())
+            .unwrap_or_default();
+        let (sprite_pack, errors) = SpritePack::parse(&text);
+        for error in errors {
+            log::warn!("保存された画像を読み込めません: {}", error);
+        }
+        sprite_pack
+    }
+    pub fn save(&self) {
+        let storage = window().and_then(|window| window.local_storage().ok().flatten());
+        if let Some(storage) = storage {
+            if storage
+                .set_item(LOCAL_STORAGE_KEY, &self.to_text())
+                .is_err()
+            {
+                log::warn!("画像を保存できませんでした。");
+            }
+        }
+    }
+    pub fn to_text(&self) -> String {
+        self.shapes
+            .iter()
+            .map(|(name, shape)| format!("[{}]\n{}\n", name, shape.to_source()))
+            .collect()
+    }
+    // 読み込めた画像と、読み込めなかった画像ごとのエラーを返す
+    // 読み込めなかった画像や含まれていない画像は組み込みの画像を使う
+    pub fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut sprite_pack = SpritePack::default();
+        let mut errors = Vec::new();
+        let mut sections: Vec<(&str, usize, String)> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            if let Some(name) = trimmed
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                sections.push((name, i + 1, String::new()));
+            } else if let Some((_, _, source)) = sections.last_mut() {
+                source.push_str(line);
+                source.push('\n');
+            } else if !trimmed.is_empty() {
+                errors.push(format!(
+                    "{}行目: 画像の名前より前にドットマップがあります",
+                    i + 1
+                ));
+            }
+        }
+        for (name, line_number, source) in sections {
+            let result = DotShape::from_source(&source)
+                .and_then(|shape| sprite_pack.set(name, shape))
+                .with_context(|| format!("{}行目の{}", line_number, name));
+            if let Err(err) = result {
+                errors.push(format!("{:#}", err));
+            }
+        }
+        (sprite_pack, errors)
+    }
+    // nameの画像を置き換える(組み込みと同じなら置き換えを取り消す)
+    pub fn set(&mut self, name: &str, shape: DotShape) -> anyhow::Result<()> {
+        ensure!(
+            name != "torchika",
+            "トーチカの形は当たり判定に使うため差し替えられません"
+        );
+        let builtin = dot_data::find_dot_data(name)
+            .filter(|_| dot_data::DOT_NAMES.contains(&name))
+            .ok_or_else(|| anyhow!("不明な画像の名前です"))?;
+        ensure!(
+            (shape.width, shape.height) == (builtin.width, builtin.height),
+            "サイズは{}x{}にしてください: {}x{}",
+            builtin.width,
+            builtin.height,
+            shape.width,
+            shape.height
+        );
+        self.shapes.retain(|(other, _)| other != name);
+        if shape != builtin {
+            self.shapes.push((name.to_string(), shape));
+        }
+        Ok(())
+    }
+    // otherに含まれる画像で置き換える
+    pub fn merge(&mut self, other: &SpritePack) {
+        for (name, shape) in &other.shapes {
+            self.shapes.retain(|(own, _)| own != name);
+            self.shapes.push((name.clone(), shape.clone()));
+        }
+    }
+    // 描画に使うnameのドットマップ
+    pub fn shape(&self, name: &str) -> DotShape {
+        self.shapes
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, shape)| shape.clone())
+            .unwrap_or_else(|| dot_data::ret_dot_data(name))
+    }
+}
+
+// urlから画像パックの文字列を取得する(ファイルが無ければNone)
+pub async fn fetch(url: &str) -> anyhow::Result<Option<String>> {
+    let js_error = |err| anyhow!("{:?}", err);
+    let response: Response = JsFuture::from(window().unwrap().fetch_with_str(url))
+        .await
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(js_error)?;
+    // 開発用サーバーは存在しないファイルの代わりにindex.htmlを返すことがある
+    let content_type = response.headers().get("content-type").ok().flatten();
+    if !response.ok() || content_type.is_some_and(|t| t.starts_with("text/html")) {
+        return Ok(None);
+    }
+    let text = JsFuture::from(response.text().map_err(js_error)?)
+        .await
+        .map_err(js_error)?;
+    Ok(text.as_string())
+}
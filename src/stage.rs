@@ -0,0 +1,201 @@
+use crate::checksum::Checksum;
+use crate::enemy::EnemyType;
+use anyhow::{bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+
+// 組み込みのステージ定義
+const EMBEDDED_STAGES: &str = include_str!("stages.json");
+// 1段に並べられるインベーダーの最大数
+pub const MAX_COLUMNS: usize = 11;
+// インベーダーの最大段数
+pub const MAX_ROWS: usize = 6;
+// 最下段のインベーダーとトーチカの間隔の最大値
+pub const MAX_START_HEIGHT: u32 = 9;
+// トーチカの最大数
+pub const MAX_TORCHIKA: usize = 6;
+// トーチカを置ける範囲(画面中央からの距離の最大値) [pixel]
+pub const MAX_TORCHIKA_OFFSET: f64 = 220.;
+
+// インベーダー1段分の並び
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Row {
+    Full(EnemyType),               // 全列が同じ種類("crab"など)
+    Cells(Vec<Option<EnemyType>>), // 左の列から順に各列の種類(nullは空き)
+}
+
+impl Row {
+    // 各列の並びから作る(全列が同じ種類ならFullにまとめる)
+    pub fn from_cells(cells: Vec<Option<EnemyType>>) -> Self {
+        match cells.first() {
+            Some(Some(first)) if cells.iter().all(|cell| *cell == Some(*first)) => {
+                Row::Full(*first)
+            }
+            _ => Row::Cells(cells),
+        }
+    }
+    pub fn cell(&self, column: usize) -> Option<EnemyType> {
+        match self {
+            Row::Full(enemy_type) => Some(*enemy_type),
+            Row::Cells(cells) => cells.get(column).copied().flatten(),
+        }
+    }
+}
+
+// 1ステージ分の定義
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Stage {
+    pub rows: Vec<Row>,     // 上の段から順に、各段のインベーダーの並び
+    pub columns: usize,     // 1段に並ぶインベーダーの数
+    pub start_height: u32,  // 開始時の最下段のインベーダーとトーチカの間隔 [インベーダー1段分]
+    pub fire_rate: f64,     // 敵の射撃頻度の倍率(1で難易度の設定どおり)
+    pub torchika: Vec<f64>, // 各トーチカの中心のx座標(画面中央からの距離) [pixel]
+}
+
+// 全ステージの定義
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StageList {
+    pub loop_to: usize, // 最終ステージの次に戻るステージ番号
+    pub stages: Vec<Stage>,
+}
+
+impl Default for StageList {
+    // 組み込みのステージ定義(不正な場合はビルドの誤りのため停止する)
+    fn default() -> Self {
+        StageList::from_json(EMBEDDED_STAGES)
+            .unwrap_or_else(|err| panic!("組み込みのステージ定義が不正です: {:#}", err))
+    }
+}
+
+impl StageList {
+    // JSON形式のステージ定義を読み込み、内容を検証する
+    pub fn from_json(text: &str) -> anyhow::Result<Self> {
+        let stage_list: StageList =
+            serde_json::from_str(text).context("ステージ定義を読み込めません")?;
+        stage_list.validate()?;
+        Ok(stage_list)
+    }
+    // 読み込める形式(JSON)に変換
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+    // 不正な箇所があれば、どのステージのどの項目か示すエラーを返す
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(!self.stages.is_empty(), "ステージが1つもありません");
+        ensure!(
+            (1..=self.stages.len()).contains(&self.loop_to),
+            "loop_toは1から{}の範囲で指定してください: {}",
+            self.stages.len(),
+            self.loop_to
+        );
+        for (i, stage) in self.stages.iter().enumerate() {
+            stage
+                .validate()
+                .with_context(|| format!("ステージ{}の定義が不正です", i + 1))?;
+        }
+        Ok(())
+    }
+    // ステージ番号(1から)に対応する定義
+    pub fn stage(&self, stage_number: usize) -> &Stage {
+        &self.stages[stage_number - 1]
+    }
+    // 次のステージ番号(最終ステージの次はloop_toに戻る)
+    pub fn next(&self, stage_number: usize) -> usize {
+        if stage_number >= self.stages.len() {
+            self.loop_to
+        } else {
+            stage_number + 1
+        }
+    }
+    // 定義のハッシュ値(リプレイを記録したときと同じステージか調べるのに使う)
+    pub fn checksum(&self) -> u64 {
+        let mut checksum = Checksum::new();
+        checksum.write_u64(self.loop_to as u64);
+        for stage in &self.stages {
+            stage.write_checksum(&mut checksum);
+        }
+        checksum.finish()
+    }
+}
+
+impl Stage {
+    // 上からrow段目、左からcolumn列目(どちらも0から)のインベーダーの種類(空きはNone)
+    pub fn cell(&self, row: usize, column: usize) -> Option<EnemyType> {
+        self.rows[row].cell(column)
+    }
+    fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            (1..=MAX_ROWS).contains(&self.rows.len()),
+            "rowsの段数は1から{}にしてください: {}",
+            MAX_ROWS,
+            self.rows.len()
+        );
+        ensure!(
+            (1..=MAX_COLUMNS).contains(&self.columns),
+            "columnsは1から{}の範囲で指定してください: {}",
+            MAX_COLUMNS,
+            self.columns
+        );
+        for (i, row) in self.rows.iter().enumerate() {
+            if let Row::Cells(cells) = row {
+                ensure!(
+                    cells.len() == self.columns,
+                    "上から{}段目の列数がcolumnsと一致しません: {}",
+                    i + 1,
+                    cells.len()
+                );
+            }
+        }
+        ensure!(
+            (0..self.rows.len())
+                .any(|row| (0..self.columns).any(|column| self.cell(row, column).is_some())),
+            "インベーダーが1体もいません"
+        );
+        ensure!(
+            self.start_height <= MAX_START_HEIGHT,
+            "start_heightは0から{}の範囲で指定してください: {}",
+            MAX_START_HEIGHT,
+            self.start_height
+        );
+        ensure!(
+            self.fire_rate.is_finite() && 0. < self.fire_rate && self.fire_rate <= 10.,
+            "fire_rateは0より大きく10以下で指定してください: {}",
+            self.fire_rate
+        );
+        ensure!(
+            self.torchika.len() <= MAX_TORCHIKA,
+            "トーチカは{}個までです: {}個",
+            MAX_TORCHIKA,
+            self.torchika.len()
+        );
+        for x in &self.torchika {
+            if !(x.is_finite() && x.abs() <= MAX_TORCHIKA_OFFSET) {
+                bail!(
+                    "トーチカの位置は-{}から{}の範囲で指定してください: {}",
+                    MAX_TORCHIKA_OFFSET,
+                    MAX_TORCHIKA_OFFSET,
+                    x
+                );
+            }
+        }
+        Ok(())
+    }
+    fn write_checksum(&self, checksum: &mut Checksum) {
+        checksum.write_u64(self.rows.len() as u64);
+        checksum.write_u64(self.columns as u64);
+        for row in 0..self.rows.len() {
+            for column in 0..self.columns {
+                let cell = self.cell(row, column);
+                checksum.write_bytes(&[cell.map_or(0, |enemy_type| enemy_type as u8 + 1)]);
+            }
+        }
+        checksum.write_u64(self.start_height as u64);
+        checksum.write_f64(self.fire_rate);
+        checksum.write_u64(self.torchika.len() as u64);
+        for x in &self.torchika {
+            checksum.write_f64(*x);
+        }
+    }
+}
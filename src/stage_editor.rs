@@ -0,0 +1,120 @@
+use crate::enemy::EnemyType;
+use crate::stage::{self, Row, Stage, StageList};
+
+// ステージエディタでの1回分の操作
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StageEdit {
+    Select(usize),            // 編集するステージを選ぶ(0から)
+    AddStage,                 // 編集中のステージを複製して次に挿入する
+    RemoveStage,              // 編集中のステージを削除する
+    LoopTo(usize),            // 最終ステージの次に戻るステージ番号
+    Brush(Option<EnemyType>), // マスに置く種類を選ぶ(Noneは空きにする)
+    Paint(usize, usize),      // 上からの段、左からの列のマスに選んだ種類を置く
+    AddRow,                   // 一番上に空の段を追加する
+    RemoveRow,                // 一番上の段を削除する
+    Columns(usize),           // 1段に並ぶ数
+    StartHeight(u32),         // 開始時の最下段とトーチカの間隔
+    FireRate(f64),            // 敵の射撃頻度の倍率
+    AddTorchika,              // 画面中央にトーチカを追加する
+    RemoveTorchika(usize),    // トーチカを削除する
+    MoveTorchika(usize, f64), // トーチカの位置(画面中央からの距離)を変える
+}
+
+// 編集中のステージ定義と、エディタの選択状態
+pub struct StageEditor {
+    pub stages: StageList,
+    pub current: usize,           // 編集中のステージ(0から)
+    pub brush: Option<EnemyType>, // マスに置く種類
+    pub message: Option<String>,  // 読み込みや適用の結果
+}
+
+impl StageEditor {
+    pub fn new(stages: StageList) -> Self {
+        StageEditor {
+            stages,
+            current: 0,
+            brush: Some(EnemyType::Octopus),
+            message: None,
+        }
+    }
+    // 編集中のステージ
+    pub fn stage(&self) -> &Stage {
+        &self.stages.stages[self.current]
+    }
+    // 編集後も各項目が読み込める範囲に収まるように丸める
+    pub fn edit(&mut self, edit: StageEdit) {
+        let stage_count = self.stages.stages.len();
+        let brush = self.brush;
+        let stage = &mut self.stages.stages[self.current];
+        match edit {
+            StageEdit::Select(index) => self.current = index.min(stage_count - 1),
+            StageEdit::AddStage => {
+                let stage = stage.clone();
+                self.current += 1;
+                self.stages.stages.insert(self.current, stage);
+            }
+            StageEdit::RemoveStage => {
+                if stage_count > 1 {
+                    self.stages.stages.remove(self.current);
+                    self.current = self.current.min(stage_count - 2);
+                    self.stages.loop_to = self.stages.loop_to.min(stage_count - 1);
+                }
+            }
+            StageEdit::LoopTo(loop_to) => self.stages.loop_to = loop_to.clamp(1, stage_count),
+            StageEdit::Brush(enemy_type) => self.brush = enemy_type,
+            StageEdit::Paint(row, column) => {
+                if row < stage.rows.len() && column < stage.columns {
+                    let mut cells: Vec<_> = (0..stage.columns)
+                        .map(|column| stage.cell(row, column))
+                        .collect();
+                    cells[column] = brush;
+                    stage.rows[row] = Row::from_cells(cells);
+                }
+            }
+            StageEdit::AddRow => {
+                if stage.rows.len() < stage::MAX_ROWS {
+                    stage.rows.insert(0, Row::Cells(vec![None; stage.columns]));
+                }
+            }
+            StageEdit::RemoveRow => {
+                if stage.rows.len() > 1 {
+                    stage.rows.remove(0);
+                }
+            }
+            StageEdit::Columns(columns) => {
+                stage.columns = columns.clamp(1, stage::MAX_COLUMNS);
+                for row in stage.rows.iter_mut() {
+                    if let Row::Cells(cells) = row {
+                        cells.resize(stage.columns, None);
+                    }
+                }
+            }
+            StageEdit::StartHeight(start_height) => {
+                stage.start_height = start_height.min(stage::MAX_START_HEIGHT)
+            }
+            StageEdit::FireRate(fire_rate) => {
+                if fire_rate.is_finite() {
+                    stage.fire_rate = fire_rate.clamp(0.1, 10.);
+                }
+            }
+            StageEdit::AddTorchika => {
+                if stage.torchika.len() < stage::MAX_TORCHIKA {
+                    stage.torchika.push(0.);
+                }
+            }
+            StageEdit::RemoveTorchika(index) => {
+                if index < stage.torchika.len() {
+                    stage.torchika.remove(index);
+                }
+            }
+            StageEdit::MoveTorchika(index, x) => {
+                if let Some(torchika) = stage.torchika.get_mut(index) {
+                    if x.is_finite() {
+                        *torchika =
+                            x.clamp(-stage::MAX_TORCHIKA_OFFSET, stage::MAX_TORCHIKA_OFFSET);
+                    }
+                }
+            }
+        }
+    }
+}
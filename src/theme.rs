@@ -0,0 +1,193 @@
+use wasm_bindgen::JsValue;
+
+// localStorageに選んだ配色を保存するときのキー
+const LOCAL_STORAGE_KEY: &str = "invader-yew-theme";
+
+// 描画に使う色の種類
+// 名前は標準の配色での色で、他の配色では別の色になる
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Turquoise,    // 青緑色(1人目のプレイヤー、カニ型インベーダー、見出し)
+    PlayerBullet, // プレイヤーの弾専用色
+    Purple,       // 紫色(タコ型インベーダー、UFO)
+    Green,        // 緑色(イカ型インベーダー)
+    Red,          // 赤色(トーチカ、爆発)
+    Yellow,       // 黄色(敵の弾)
+    Orange,       // 橙色(2人目のプレイヤー)
+    Background,   // 背景色
+    Text,         // 得点などの文字
+    Message,      // タイトルやゲームオーバーの文字
+    GroundLine,   // プレイヤーの下の線
+}
+
+// 画面の配色(各色のrgbaの値)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub turquoise: [u8; 4],
+    pub player_bullet: [u8; 4],
+    pub purple: [u8; 4],
+    pub green: [u8; 4],
+    pub red: [u8; 4],
+    pub yellow: [u8; 4],
+    pub orange: [u8; 4],
+    pub background: [u8; 4],
+    pub text: [u8; 4],
+    pub message: [u8; 4],
+    pub ground_line: [u8; 4],
+}
+
+impl Theme {
+    // 指定した色に対応するrgbaの値を返す
+    pub fn rgba(&self, color: Color) -> [u8; 4] {
+        match color {
+            Color::Turquoise => self.turquoise,
+            Color::PlayerBullet => self.player_bullet,
+            Color::Purple => self.purple,
+            Color::Green => self.green,
+            Color::Red => self.red,
+            Color::Yellow => self.yellow,
+            Color::Orange => self.orange,
+            Color::Background => self.background,
+            Color::Text => self.text,
+            Color::Message => self.message,
+            Color::GroundLine => self.ground_line,
+        }
+    }
+    // 指定した色をキャンバスの塗りつぶし色として返す
+    pub fn style(&self, color: Color) -> JsValue {
+        let [r, g, b, _] = self.rgba(color);
+        JsValue::from(format!("rgb({},{},{})", r, g, b))
+    }
+    // 背景色以外をすべて白にした配色(セロハンで色を付けるときに使う)
+    pub fn monochrome(&self) -> Theme {
+        let white = [255, 255, 255, 255];
+        Theme {
+            turquoise: white,
+            player_bullet: white,
+            purple: white,
+            green: white,
+            red: white,
+            yellow: white,
+            orange: white,
+            background: self.background,
+            text: white,
+            message: white,
+            ground_line: white,
+        }
+    }
+}
+
+// 設定画面で選ぶ配色
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemePreset {
+    Classic,
+    Deuteranopia, // 2型色覚(緑が見分けにくい)向け
+    Protanopia,   // 1型色覚(赤が暗く見える)向け
+    HighContrast,
+}
+
+impl ThemePreset {
+    pub const ALL: [ThemePreset; 4] = [
+        ThemePreset::Classic,
+        ThemePreset::Deuteranopia,
+        ThemePreset::Protanopia,
+        ThemePreset::HighContrast,
+    ];
+    // 保存するときの名前
+    pub fn name(self) -> &'static str {
+        match self {
+            ThemePreset::Classic => "classic",
+            ThemePreset::Deuteranopia => "deuteranopia",
+            ThemePreset::Protanopia => "protanopia",
+            ThemePreset::HighContrast => "high-contrast",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        ThemePreset::ALL
+            .into_iter()
+            .find(|preset| preset.name() == name)
+    }
+    // 設定画面に表示する名前
+    pub fn label(self) -> &'static str {
+        match self {
+            ThemePreset::Classic => "Classic",
+            ThemePreset::Deuteranopia => "Deuteranopia",
+            ThemePreset::Protanopia => "Protanopia",
+            ThemePreset::HighContrast => "High Contrast",
+        }
+    }
+    // 保存された配色を読み込む(無ければ標準の配色)
+    pub fn load() -> Self {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        storage
+            .and_then(|storage| storage.get_item(LOCAL_STORAGE_KEY).ok().flatten())
+            .and_then(|name| ThemePreset::from_name(&name))
+            .unwrap_or(ThemePreset::Classic)
+    }
+    pub fn save(self) {
+        let storage = web_sys::window().and_then(|window| window.local_storage().ok().flatten());
+        if let Some(storage) = storage {
+            if storage.set_item(LOCAL_STORAGE_KEY, self.name()).is_err() {
+                log::warn!("配色を保存できませんでした。");
+            }
+        }
+    }
+    pub fn theme(self) -> Theme {
+        match self {
+            ThemePreset::Classic => Theme {
+                turquoise: [68, 200, 210, 255],
+                player_bullet: [65, 199, 209, 255],
+                purple: [219, 85, 221, 255],
+                green: [98, 222, 109, 255],
+                red: [210, 0, 0, 255],
+                yellow: [190, 180, 80, 255],
+                orange: [230, 140, 50, 255],
+                background: [0, 0, 0, 255],
+                text: [255, 255, 255, 255],
+                message: [200, 10, 10, 255],
+                ground_line: [180, 0, 0, 255],
+            },
+            // 赤と緑の組み合わせを避け、青と黄色の明るさの差で見分けられるようにする
+            ThemePreset::Deuteranopia => Theme {
+                turquoise: [86, 180, 233, 255],
+                player_bullet: [86, 180, 233, 255],
+                purple: [204, 121, 167, 255],
+                green: [240, 228, 66, 255],
+                red: [213, 94, 0, 255],
+                yellow: [235, 235, 235, 255],
+                orange: [230, 159, 0, 255],
+                background: [0, 0, 0, 255],
+                text: [255, 255, 255, 255],
+                message: [213, 94, 0, 255],
+                ground_line: [213, 94, 0, 255],
+            },
+            // 赤は黒い背景に沈まないように明るい橙色にする
+            ThemePreset::Protanopia => Theme {
+                turquoise: [86, 180, 233, 255],
+                player_bullet: [86, 180, 233, 255],
+                purple: [170, 150, 255, 255],
+                green: [240, 228, 66, 255],
+                red: [255, 150, 40, 255],
+                yellow: [235, 235, 235, 255],
+                orange: [204, 121, 167, 255],
+                background: [0, 0, 0, 255],
+                text: [255, 255, 255, 255],
+                message: [255, 150, 40, 255],
+                ground_line: [255, 150, 40, 255],
+            },
+            ThemePreset::HighContrast => Theme {
+                turquoise: [0, 255, 255, 255],
+                player_bullet: [255, 255, 255, 255],
+                purple: [255, 0, 255, 255],
+                green: [0, 255, 0, 255],
+                red: [255, 60, 60, 255],
+                yellow: [255, 255, 0, 255],
+                orange: [255, 160, 0, 255],
+                background: [0, 0, 0, 255],
+                text: [255, 255, 255, 255],
+                message: [255, 255, 0, 255],
+                ground_line: [255, 255, 255, 255],
+            },
+        }
+    }
+}
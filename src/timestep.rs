@@ -0,0 +1,40 @@
+// ゲームの更新周期(60Hz) [ms]
+pub const STEP_MS: f64 = 1000. / 60.;
+// 1回の描画で進める最大フレーム数(これを超えた分の遅れは切り捨てる)
+const MAX_STEPS_PER_RENDER: usize = 5;
+// 経過時間の上限(タブが非表示で停止していた場合など) [ms]
+const MAX_ELAPSED_MS: f64 = 250.;
+
+// 描画の周期に関係なく、一定の周期でゲームを進めるためのタイマー
+pub struct FixedTimestep {
+    pre_time: Option<f64>, // 前回呼び出された時刻 [ms]
+    accumulator: f64,      // まだゲームに反映していない経過時間 [ms]
+}
+
+impl FixedTimestep {
+    pub fn new() -> Self {
+        FixedTimestep {
+            pre_time: None,
+            accumulator: 0.,
+        }
+    }
+    // 現在時刻nowまでに進めるべきフレーム数を返す
+    pub fn advance(&mut self, now: f64) -> usize {
+        let elapsed = match self.pre_time {
+            Some(pre_time) => (now - pre_time).clamp(0., MAX_ELAPSED_MS),
+            // 初回は1フレームだけ進める
+            None => STEP_MS,
+        };
+        self.pre_time = Some(now);
+        self.accumulator += elapsed;
+
+        let steps = (self.accumulator / STEP_MS).floor() as usize;
+        self.accumulator -= steps as f64 * STEP_MS;
+        if steps > MAX_STEPS_PER_RENDER {
+            // 追いつけない分は捨てて、遅れを持ち越さない
+            self.accumulator = 0.;
+            return MAX_STEPS_PER_RENDER;
+        }
+        steps
+    }
+}
@@ -1,9 +1,13 @@
+use crate::difficulty::Difficulty;
+use crate::game::PlayMode;
+use crate::input::{Action, InputMap};
 use crate::math::Vec2;
-use wasm_bindgen::JsValue;
-use web_sys::CanvasRenderingContext2d;
+use crate::renderer::{Renderer, TextAlign};
+use crate::theme::Color;
 
 pub struct Title {
     pos: Vec2,
+    pub difficulty_key: String, // 難易度を切り替えるキーの表示名
 }
 
 impl Title {
@@ -11,16 +15,57 @@ impl Title {
         Title {
             // 大体キャンパスの中心上に設定
             pos: Vec2::new(canvas_width / 2., canvas_height / 4.),
+            difficulty_key: InputMap::default().key_label(Action::Difficulty),
         }
     }
-    pub fn render(&self, ctx: &CanvasRenderingContext2d) {
-        ctx.set_font("90px monospace");
-        ctx.set_fill_style(&JsValue::from("rgba(200, 10, 10)"));
-        ctx.fill_text("Invader", self.pos.x - 170., self.pos.y)
-            .unwrap();
+    pub fn render(&self, renderer: &mut dyn Renderer, play_mode: PlayMode, difficulty: Difficulty) {
+        renderer.fill_text(
+            "Invader",
+            self.pos.x - 170.,
+            self.pos.y,
+            90.,
+            TextAlign::Start,
+            Color::Message,
+        );
+        renderer.fill_text(
+            "Press Enter",
+            self.pos.x - 120.,
+            self.pos.y + 80.,
+            40.,
+            TextAlign::Start,
+            Color::Message,
+        );
 
-        ctx.set_font("40px monospace");
-        ctx.fill_text("Press Enter", self.pos.x - 120., self.pos.y + 80.)
-            .unwrap();
+        // 左右で選ぶ遊び方(選択中のものに印を付ける)
+        for (i, mode) in PlayMode::ALL.iter().enumerate() {
+            let text = match mode {
+                PlayMode::Single => "1 PLAYER",
+                PlayMode::Alternate => "2 PLAYERS",
+                PlayMode::CoOp => "2P CO-OP",
+            };
+            let mark = if play_mode == *mode { ">" } else { " " };
+            renderer.fill_text(
+                &format!("{}{}", mark, text),
+                self.pos.x - 250. + 170. * i as f64,
+                self.pos.y + 125.,
+                22.,
+                TextAlign::Start,
+                Color::Text,
+            );
+        }
+
+        // 割り当てたキーで切り替える難易度
+        renderer.fill_text(
+            &format!(
+                "DIFFICULTY: {} ({})",
+                difficulty.label(),
+                self.difficulty_key
+            ),
+            self.pos.x,
+            self.pos.y + 162.,
+            20.,
+            TextAlign::Center,
+            Color::Text,
+        );
     }
 }
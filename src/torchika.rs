@@ -0,0 +1,113 @@
+use crate::checksum::Checksum;
+use crate::dot_data::{self, DotShape};
+use crate::math::Vec2;
+
+// 表示サイズ/オリジナルの画像サイズ
+const SCALE: f64 = 3.;
+
+pub struct Torchika {
+    pub pos: Vec2, // 左上の位置
+    pub scale: f64,
+    pub dot_map: Vec<Vec<bool>>, // 残っている部分を真、破壊された部分を偽で表す
+}
+
+impl Torchika {
+    fn new(pos: Vec2) -> Self {
+        Torchika {
+            pos,
+            scale: SCALE,
+            dot_map: dot_data::ret_dot_data("torchika").dot_map,
+        }
+    }
+    pub fn width(&self) -> f64 {
+        self.dot_map[0].len() as f64 * self.scale
+    }
+    // 指定座標に対応するドットの位置を返す(範囲外ならばNone)
+    fn dot_index(&self, pos: &Vec2) -> Option<(usize, usize)> {
+        let x = ((pos.x - self.pos.x) / self.scale).floor();
+        let y = ((pos.y - self.pos.y) / self.scale).floor();
+        if x < 0. || y < 0. || x >= self.dot_map[0].len() as f64 || y >= self.dot_map.len() as f64 {
+            return None;
+        }
+        Some((x as usize, y as usize))
+    }
+    // 指定座標にトーチカの破壊されていない部分があれば真を返す
+    fn hit(&self, pos: &Vec2) -> bool {
+        match self.dot_index(pos) {
+            Some((x, y)) => self.dot_map[y][x],
+            None => false,
+        }
+    }
+    // 各ドットの中心座標が条件を満たす部分を削る
+    fn erode_by(&mut self, is_erased: impl Fn(Vec2) -> bool) {
+        for (y, line) in self.dot_map.iter_mut().enumerate() {
+            for (x, dot) in line.iter_mut().enumerate() {
+                let center = Vec2::new(
+                    self.pos.x + (x as f64 + 0.5) * self.scale,
+                    self.pos.y + (y as f64 + 0.5) * self.scale,
+                );
+                if *dot && is_erased(center) {
+                    *dot = false;
+                }
+            }
+        }
+    }
+    // 状態のハッシュ値を計算する
+    pub fn write_checksum(&self, checksum: &mut Checksum) {
+        checksum.write_vec2(&self.pos);
+        for line in &self.dot_map {
+            for dot in line {
+                checksum.write_bool(*dot);
+            }
+        }
+    }
+}
+
+// ステージ開始時のトーチカを返す
+// offsetsは各トーチカの中心のx座標(画面中央からの距離)
+pub fn ret_torchika_list(canvas_width: f64, canvas_height: f64, offsets: &[f64]) -> Vec<Torchika> {
+    offsets
+        .iter()
+        .map(|offset| {
+            let mut torchika = Torchika::new(Vec2::new(0., canvas_height - 180.));
+            torchika.pos.x = canvas_width / 2. + offset - torchika.width() / 2.;
+            torchika
+        })
+        .collect()
+}
+
+// 指定した座標のどれか一つにでもトーチカが存在すれば真を返す
+pub fn collision(torchika_list: &[Torchika], pos_list: &[Vec2]) -> bool {
+    torchika_list
+        .iter()
+        .any(|torchika| pos_list.iter().any(|pos| torchika.hit(pos)))
+}
+
+// 中心位置centerに拡大率scaleで置いたドットマップの描画部分でトーチカを削る
+pub fn erode_with_shape(
+    torchika_list: &mut [Torchika],
+    center: Vec2,
+    shape: &DotShape,
+    scale: f64,
+) {
+    let left = center.x - shape.width as f64 * scale / 2.;
+    let top = center.y - shape.height as f64 * scale / 2.;
+    for torchika in torchika_list.iter_mut() {
+        torchika.erode_by(|pos| {
+            let x = ((pos.x - left) / scale).floor();
+            let y = ((pos.y - top) / scale).floor();
+            0. <= x
+                && 0. <= y
+                && x < shape.width as f64
+                && y < shape.height as f64
+                && shape.dot_map[y as usize][x as usize]
+        });
+    }
+}
+
+// 中心位置center、幅width、高さheightの矩形の範囲のトーチカを削る
+pub fn erode_with_rect(torchika_list: &mut [Torchika], center: Vec2, width: f64, height: f64) {
+    for torchika in torchika_list.iter_mut() {
+        torchika.erode_by(|pos| pos.collision(&center, width, height));
+    }
+}
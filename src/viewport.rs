@@ -0,0 +1,142 @@
+use wasm_bindgen::{prelude::Closure, JsCast};
+use web_sys::{window, HtmlCanvasElement};
+use yew::Callback;
+
+// localStorageに選んだ拡大方法を保存するときのキー
+const LOCAL_STORAGE_KEY: &str = "invader-yew-scale-mode";
+// ゲームを描く論理的な解像度 [pixel]
+// ゲーム内の座標はすべてこの大きさの画面を基準にし、表示するときに拡大する
+pub const LOGICAL_WIDTH: u32 = 540;
+pub const LOGICAL_HEIGHT: u32 = 600;
+// 全画面表示にする要素(キャンバスを囲む)のid
+pub const GAME_SCREEN_ID: &str = "game-screen";
+// キャンバスの下に置くタッチ操作用のボタンを囲む要素のid
+pub const TOUCH_CONTROLS_ID: &str = "touch-controls";
+
+// ウィンドウに合わせて画面を拡大する方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    Integer, // ドットの大きさが揃うように整数倍で拡大する
+    Fit,     // 縦横比を保ったまま、収まる最大の大きさに拡大する
+}
+
+impl ScaleMode {
+    pub const ALL: [ScaleMode; 2] = [ScaleMode::Integer, ScaleMode::Fit];
+    // 保存するときの名前
+    pub fn name(self) -> &'static str {
+        match self {
+            ScaleMode::Integer => "integer",
+            ScaleMode::Fit => "fit",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        ScaleMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+    // 設定画面に表示する名前
+    pub fn label(self) -> &'static str {
+        match self {
+            ScaleMode::Integer => "Integer",
+            ScaleMode::Fit => "Fit",
+        }
+    }
+    // 保存された拡大方法を読み込む(無ければ整数倍)
+    pub fn load() -> Self {
+        let storage = window().and_then(|window| window.local_storage().ok().flatten());
+        storage
+            .and_then(|storage| storage.get_item(LOCAL_STORAGE_KEY).ok().flatten())
+            .and_then(|name| ScaleMode::from_name(&name))
+            .unwrap_or(ScaleMode::Integer)
+    }
+    pub fn save(self) {
+        let storage = window().and_then(|window| window.local_storage().ok().flatten());
+        if let Some(storage) = storage {
+            if storage.set_item(LOCAL_STORAGE_KEY, self.name()).is_err() {
+                log::warn!("拡大方法を保存できませんでした。");
+            }
+        }
+    }
+    // 幅width、高さheight [CSS pixel]の範囲に収まる画面の表示サイズ [CSS pixel]
+    // 整数倍は1CSS pixelあたりdevice_pixel_ratio個ある実際の画素で数える
+    pub fn display_size(self, width: f64, height: f64, device_pixel_ratio: f64) -> (f64, f64) {
+        let fit = (width / LOGICAL_WIDTH as f64).min(height / LOGICAL_HEIGHT as f64);
+        let scale = match self {
+            ScaleMode::Integer => {
+                let pixels = (fit * device_pixel_ratio).floor();
+                // 等倍でも収まらない場合は縮小する
+                if pixels >= 1. {
+                    pixels / device_pixel_ratio
+                } else {
+                    fit
+                }
+            }
+            ScaleMode::Fit => fit,
+        };
+        (LOGICAL_WIDTH as f64 * scale, LOGICAL_HEIGHT as f64 * scale)
+    }
+}
+
+// ウィンドウ(全画面表示中は画面全体)に収まるようにキャンバスの表示サイズを決め直す
+// キャンバスの画素数は論理的な解像度のまま変えない
+pub fn layout(canvas: &HtmlCanvasElement, scale_mode: ScaleMode) {
+    let window = window().unwrap();
+    let document = window.document().unwrap();
+    let Some(root) = document.document_element() else {
+        return;
+    };
+    let mut width = root.client_width() as f64;
+    let mut height = root.client_height() as f64;
+    if document.fullscreen_element().is_none() {
+        // 縦長の画面では横幅に合わせ、論理的な解像度より大きくしない
+        if height > width {
+            width = width.min(LOGICAL_WIDTH as f64);
+        }
+        // タッチ操作用のボタンがキャンバスと一緒に画面に収まるように、その分を空ける
+        // (表示していなければ大きさが0になり、空けない)
+        if let Some(touch_controls) = document.get_element_by_id(TOUCH_CONTROLS_ID) {
+            let below = touch_controls.get_bounding_client_rect().bottom()
+                - canvas.get_bounding_client_rect().bottom();
+            height -= below.max(0.);
+        }
+    }
+    let (width, height) = scale_mode.display_size(width, height, window.device_pixel_ratio());
+    let style = canvas.style();
+    if style
+        .set_property("width", &format!("{}px", width))
+        .is_err()
+        || style
+            .set_property("height", &format!("{}px", height))
+            .is_err()
+    {
+        log::warn!("キャンバスの表示サイズを変更できませんでした。");
+    }
+}
+
+// ウィンドウの大きさや全画面表示が変わるとon_resizeを呼ぶ
+pub fn setup(on_resize: Callback<()>) {
+    let window = window().unwrap();
+    let document = window.document().unwrap();
+    let closure_resize = Closure::wrap(Box::new(move || on_resize.emit(())) as Box<dyn FnMut()>);
+    window
+        .add_event_listener_with_callback("resize", closure_resize.as_ref().unchecked_ref())
+        .unwrap();
+    document
+        .add_event_listener_with_callback(
+            "fullscreenchange",
+            closure_resize.as_ref().unchecked_ref(),
+        )
+        .unwrap();
+    closure_resize.forget();
+}
+
+// 全画面表示を切り替える
+// ブラウザはキー入力やクリックの処理中にしか全画面表示を許さないため、その中で呼ぶ
+pub fn toggle_fullscreen() {
+    let document = window().unwrap().document().unwrap();
+    if document.fullscreen_element().is_some() {
+        document.exit_fullscreen();
+    } else if let Some(game_screen) = document.get_element_by_id(GAME_SCREEN_ID) {
+        if game_screen.request_fullscreen().is_err() {
+            log::warn!("全画面表示にできませんでした。");
+        }
+    }
+}